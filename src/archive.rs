@@ -0,0 +1,584 @@
+use std::{
+    fs,
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Component, Path, PathBuf},
+    process,
+};
+
+/// The supported archive formats; tarballs may be compressed by an external
+/// decompressor.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Zip,
+    Tar(Option<&'static str>),
+    SevenZip,
+}
+
+const SUFFIXES: [(&str, Kind); 11] = [
+    (".zip", Kind::Zip),
+    (".7z", Kind::SevenZip),
+    (".tar", Kind::Tar(None)),
+    (".tar.gz", Kind::Tar(Some("gzip"))),
+    (".tgz", Kind::Tar(Some("gzip"))),
+    (".tar.bz2", Kind::Tar(Some("bzip2"))),
+    (".tbz2", Kind::Tar(Some("bzip2"))),
+    (".tar.xz", Kind::Tar(Some("xz"))),
+    (".txz", Kind::Tar(Some("xz"))),
+    (".tar.zst", Kind::Tar(Some("zstd"))),
+    (".tzst", Kind::Tar(Some("zstd"))),
+];
+
+fn suffix_of(path: &Path) -> Option<(&'static str, Kind)> {
+    let name = path.file_name()?.to_str()?.to_lowercase();
+
+    SUFFIXES
+        .iter()
+        .filter(|(suffix, _)| name.len() > suffix.len() && name.ends_with(suffix))
+        .max_by_key(|(suffix, _)| suffix.len())
+        .copied()
+}
+
+/// Returns the kind of the [path] archive by its name; None, if it is no archive.
+///
+pub fn kind(path: &Path) -> Option<Kind> {
+    suffix_of(path).map(|(_, kind)| kind)
+}
+
+/// Returns true, if an entry of the [path] archive can be copied out
+/// (see entry_copy()) without decompressing the ones in front of it:
+/// ZIP, and plain tar archives.
+///
+pub fn is_random(path: &Path) -> bool {
+    matches!(kind(path), Some(Kind::Zip | Kind::Tar(None)))
+}
+
+/// An archive file, to be copied out of the archive by entry_copy().
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    data: Option<(u64, u64)>, // Offset and length in the archive, if stored as is.
+}
+
+/// Returns the name of the [path] archive, the archive suffix dropped,
+/// e.g. "Book" of "Book.tar.gz".
+///
+pub fn stem(path: &Path) -> Option<String> {
+    let (suffix, _) = suffix_of(path)?;
+    let name = path.file_name()?.to_str()?;
+
+    Some(name[..name.len() - suffix.len()].to_string())
+}
+
+/// Returns the path of the [name] entry under [to]; None for the entries,
+/// reaching out of [to].
+///
+fn entry_path(to: &Path, name: &str) -> Option<PathBuf> {
+    let path = Path::new(name);
+
+    if path
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        Some(to.join(path))
+    } else {
+        None
+    }
+}
+
+fn is_wanted(name: &str, exts: &[&str]) -> bool {
+    !name.ends_with('/')
+        && Path::new(name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| exts.iter().any(|x| x.eq_ignore_ascii_case(e)))
+}
+
+/// Creates the [name] entry file under [to], its directories included.
+///
+fn entry_create(to: &Path, name: &str) -> io::Result<fs::File> {
+    let path =
+        entry_path(to, name).ok_or_else(|| io::Error::other(format!("bad entry \"{}\"", name)))?;
+
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::File::create(&path)
+}
+
+/// Writes [len] bytes of [from] to [to].
+///
+fn data_write(from: &mut impl Read, len: u64, to: &mut impl Write) -> io::Result<()> {
+    if io::copy(&mut from.take(len), to)? < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// Writes [len] bytes of [from] to the [name] entry file under [to].
+///
+fn entry_write(from: &mut impl Read, len: u64, to: &Path, name: &str) -> io::Result<()> {
+    data_write(from, len, &mut entry_create(to, name)?)
+}
+
+fn tar_number(field: &[u8]) -> u64 {
+    if field[0] & 0x80 != 0 {
+        // Base-256, for the large ones.
+        return field[1..].iter().fold(0, |n, b| (n << 8) | *b as u64);
+    }
+    let text = String::from_utf8_lossy(field);
+    u64::from_str_radix(text.trim_matches(|c: char| c == '\0' || c == ' '), 8).unwrap_or(0)
+}
+
+fn c_str(field: &[u8]) -> String {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+/// Returns the path of a pax extended header [records], if any.
+///
+fn pax_path(records: &[u8]) -> Option<String> {
+    let mut rest = records;
+
+    while !rest.is_empty() {
+        let space = rest.iter().position(|b| *b == b' ')?;
+        let len: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+        let record = rest.get(space + 1..len)?;
+        if let Some(path) = record.strip_prefix(b"path=") {
+            return Some(String::from_utf8_lossy(path.strip_suffix(b"\n")?).to_string());
+        }
+        rest = &rest[len..];
+    }
+    None
+}
+
+/// Goes through the regular files of the [tar] stream, entry after entry,
+/// handing the name, size, offset in the stream, and data of each one
+/// to [each], which may leave the data unread.
+///
+fn tar_walk(
+    tar: impl Read,
+    mut each: impl FnMut(&str, u64, u64, &mut dyn Read) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut tar = BufReader::new(tar);
+    let mut header = [0_u8; 512];
+    let mut long_name = None;
+    let mut offset = 0;
+
+    loop {
+        match tar.read_exact(&mut header) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            result => result?,
+        }
+        if header.iter().all(|b| *b == 0) {
+            break;
+        }
+        let size = tar_number(&header[124..136]);
+        let padding = (512 - size % 512) % 512;
+        offset += 512;
+        let name = long_name.take().unwrap_or_else(|| {
+            let (prefix, name) = (c_str(&header[345..500]), c_str(&header[..100]));
+            if &header[257..262] == b"ustar" && !prefix.is_empty() {
+                format!("{}/{}", prefix, name)
+            } else {
+                name
+            }
+        });
+        let mut data = (&mut tar).take(size);
+
+        match header[156] {
+            b'L' | b'x' => {
+                let mut records = vec![];
+                data.read_to_end(&mut records)?;
+                long_name = if header[156] == b'L' {
+                    Some(c_str(&records))
+                } else {
+                    pax_path(&records)
+                };
+            }
+            b'0' | b'7' | 0 => each(&name, size, offset, &mut data)?,
+            _ => (),
+        }
+        io::copy(&mut data, &mut io::sink())?;
+        io::copy(&mut (&mut tar).take(padding), &mut io::sink())?;
+        offset += size + padding;
+    }
+    Ok(())
+}
+
+/// Writes the regular files of the [tar] stream, having the [exts] extensions,
+/// under [to], entry after entry. Returns the number of the files written.
+///
+pub fn tar_unpack(tar: impl Read, to: &Path, exts: &[&str]) -> io::Result<u64> {
+    tar_unpack_each(tar, to, exts, |_, _| Ok(()))
+}
+
+/// Does tar_unpack(), handing each file to [each], as soon as it is written,
+/// along with its entry.
+///
+fn tar_unpack_each(
+    tar: impl Read,
+    to: &Path,
+    exts: &[&str],
+    mut each: impl FnMut(&Path, Entry) -> io::Result<()>,
+) -> io::Result<u64> {
+    let mut count = 0;
+
+    tar_walk(tar, |name, size, offset, mut data| {
+        if is_wanted(name, exts) {
+            entry_write(&mut data, size, to, name)?;
+            each(
+                &entry_path(to, name).unwrap(),
+                Entry {
+                    name: name.to_string(),
+                    data: Some((offset, size)),
+                },
+            )?;
+            count += 1;
+        }
+        Ok(())
+    })?;
+    Ok(count)
+}
+
+/// Returns the regular files of the [tar] stream, having the [exts] extensions:
+/// (name, size).
+///
+pub fn tar_list(tar: impl Read, exts: &[&str]) -> io::Result<Vec<(String, u64)>> {
+    let mut list = vec![];
+
+    tar_walk(tar, |name, size, _, _| {
+        if is_wanted(name, exts) {
+            list.push((name.to_string(), size));
+        }
+        Ok(())
+    })?;
+    Ok(list)
+}
+
+fn u16_at(b: &[u8], at: usize) -> u64 {
+    u16::from_le_bytes([b[at], b[at + 1]]) as u64
+}
+
+fn u32_at(b: &[u8], at: usize) -> u64 {
+    u32::from_le_bytes(b[at..at + 4].try_into().unwrap()) as u64
+}
+
+/// A ZIP archive entry, as listed in the central directory.
+///
+struct ZipEntry {
+    name: String,
+    method: u64,
+    size: u64, // Compressed.
+    len: u64,  // Uncompressed.
+    offset: u64,
+}
+
+/// Returns the entries of the [path] ZIP archive.
+///
+fn zip_entries(path: &Path) -> io::Result<Vec<ZipEntry>> {
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    let tail_len = len.min(22 + 0xffff);
+    let mut tail = vec![0; tail_len as usize];
+
+    file.seek(SeekFrom::Start(len - tail_len))?;
+    file.read_exact(&mut tail)?;
+    let end = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|i| tail[*i..].starts_with(b"PK\x05\x06"))
+        .ok_or_else(|| io::Error::other("no ZIP central directory"))?;
+    let (entries, dir_size, dir_offset) = (
+        u16_at(&tail, end + 10),
+        u32_at(&tail, end + 12),
+        u32_at(&tail, end + 16),
+    );
+    if entries == 0xffff || dir_offset == 0xffff_ffff {
+        return Err(io::Error::other("ZIP64 archives are not supported"));
+    }
+
+    let mut dir = vec![0; dir_size as usize];
+    file.seek(SeekFrom::Start(dir_offset))?;
+    file.read_exact(&mut dir)?;
+    let mut list = vec![];
+    let mut at = 0;
+
+    for _ in 0..entries {
+        let header = dir
+            .get(at..at + 46)
+            .filter(|h| h.starts_with(b"PK\x01\x02"))
+            .ok_or_else(|| io::Error::other("bad ZIP central directory"))?;
+        let (name_len, extra_len, comment_len) = (
+            u16_at(header, 28) as usize,
+            u16_at(header, 30) as usize,
+            u16_at(header, 32) as usize,
+        );
+        let name = dir
+            .get(at + 46..at + 46 + name_len)
+            .ok_or_else(|| io::Error::other("bad ZIP central directory"))?;
+
+        list.push(ZipEntry {
+            name: String::from_utf8_lossy(name).replace('\\', "/"),
+            method: u16_at(header, 10),
+            size: u32_at(header, 20),
+            len: u32_at(header, 24),
+            offset: u32_at(header, 42),
+        });
+        at += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(list)
+}
+
+/// Returns [name] as an unzip pattern, matching the name only.
+///
+fn unzip_pattern(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '[' | '*' | '?' => format!("[{}]", c),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Writes the [name] file of the [path] ZIP archive to [to] through unzip.
+///
+fn unzip_entry(path: &Path, name: &str, to: &mut impl Write) -> io::Result<()> {
+    let mut child = process::Command::new("unzip")
+        .arg("-p")
+        .arg(path)
+        .arg(unzip_pattern(name))
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::piped())
+        .spawn()?;
+
+    io::copy(&mut child.stdout.take().unwrap(), to)?;
+    if !child.wait()?.success() {
+        return Err(io::Error::other(format!("unzip failed on \"{}\"", name)));
+    }
+    Ok(())
+}
+
+/// Writes the files of the [path] ZIP archive, having the [exts] extensions,
+/// under [to]: the stored ones straight from the archive, the deflated ones
+/// through unzip, entry after entry, handing each one to [each], as soon
+/// as it is written. Returns the number of the files written.
+///
+fn zip_unpack(
+    path: &Path,
+    to: &Path,
+    exts: &[&str],
+    mut each: impl FnMut(&Path, Entry) -> io::Result<()>,
+) -> io::Result<u64> {
+    let mut file = fs::File::open(path)?;
+    let mut count = 0;
+
+    for entry in zip_entries(path)? {
+        if !is_wanted(&entry.name, exts) {
+            continue;
+        }
+        let data = match entry.method {
+            0 => {
+                let mut local = [0; 30];
+                file.seek(SeekFrom::Start(entry.offset))?;
+                file.read_exact(&mut local)?;
+                let offset = entry.offset + 30 + u16_at(&local, 26) + u16_at(&local, 28);
+
+                file.seek(SeekFrom::Start(offset))?;
+                entry_write(&mut file, entry.size, to, &entry.name)?;
+                Some((offset, entry.size))
+            }
+            _ => {
+                unzip_entry(path, &entry.name, &mut entry_create(to, &entry.name)?)?;
+                None
+            }
+        };
+        each(
+            &entry_path(to, &entry.name).unwrap(),
+            Entry {
+                name: entry.name,
+                data,
+            },
+        )?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Writes the files of the [path] 7z archive, having the [exts] extensions,
+/// under [to], by 7-Zip in one pass. Returns the number of the files written.
+///
+fn seven_zip_unpack(path: &Path, to: &Path, exts: &[&str]) -> io::Result<u64> {
+    let mut tried = Err(io::ErrorKind::NotFound.into());
+
+    for program in ["7z", "7za", "7zz"] {
+        tried = process::Command::new(program)
+            .args(["x", "-y", "-bd", "-ssc-"])
+            .arg(format!("-o{}", to.display()))
+            .arg(path)
+            .args(exts.iter().map(|e| format!("-ir!*.{}", e)))
+            .stdin(process::Stdio::null())
+            .stdout(process::Stdio::null())
+            .status();
+        if !tried
+            .as_ref()
+            .is_err_and(|e| e.kind() == io::ErrorKind::NotFound)
+        {
+            break;
+        }
+    }
+    if !tried?.success() {
+        return Err(io::Error::other("7-Zip failed"));
+    }
+    Ok(files_count(to))
+}
+
+/// Returns the files of the [path] 7z archive, having the [exts] extensions,
+/// as 7-Zip lists them: (name, size).
+///
+fn seven_zip_list(path: &Path, exts: &[&str]) -> io::Result<Vec<(String, u64)>> {
+    let mut tried = Err(io::ErrorKind::NotFound.into());
+
+    for program in ["7z", "7za", "7zz"] {
+        tried = process::Command::new(program)
+            .args(["l", "-slt", "-ba"])
+            .arg(path)
+            .stdin(process::Stdio::null())
+            .output();
+        if !tried
+            .as_ref()
+            .is_err_and(|e| e.kind() == io::ErrorKind::NotFound)
+        {
+            break;
+        }
+    }
+    let out = tried?;
+    if !out.status.success() {
+        return Err(io::Error::other("7-Zip failed"));
+    }
+
+    // Blank line separated blocks of "Key = value" lines, an entry each.
+    let text = String::from_utf8_lossy(&out.stdout);
+    let mut list = vec![];
+    for block in text.split("\n\n") {
+        let value = |key: &str| {
+            block
+                .lines()
+                .find_map(|l| l.strip_prefix(key)?.strip_prefix(" = "))
+        };
+        if let (Some(name), Some(size)) = (value("Path"), value("Size")) {
+            if value("Folder") != Some("+") && is_wanted(name, exts) {
+                list.push((name.to_string(), size.trim().parse().unwrap_or(0)));
+            }
+        }
+    }
+    Ok(list)
+}
+
+fn files_count(dir: &Path) -> u64 {
+    fs::read_dir(dir).map_or(0, |entries| {
+        entries
+            .flatten()
+            .map(|e| match e.file_type() {
+                Ok(t) if t.is_dir() => files_count(&e.path()),
+                Ok(_) => 1,
+                Err(_) => 0,
+            })
+            .sum()
+    })
+}
+
+/// Writes the files of the [path] archive, having the [exts] extensions, under [to],
+/// keeping the directory structure; the rest of the archive is never written
+/// to disk. Returns the number of the files written.
+///
+pub fn unpack(path: &Path, to: &Path, exts: &[&str]) -> io::Result<u64> {
+    fs::create_dir_all(to)?;
+
+    match kind(path) {
+        Some(Kind::Zip) => zip_unpack(path, to, exts, |_, _| Ok(())),
+        Some(Kind::SevenZip) => seven_zip_unpack(path, to, exts),
+        Some(Kind::Tar(None)) => tar_unpack(fs::File::open(path)?, to, exts),
+        Some(Kind::Tar(Some(decompressor))) => {
+            let mut child = process::Command::new(decompressor)
+                .arg("-dc")
+                .arg(path)
+                .stdin(process::Stdio::null())
+                .stdout(process::Stdio::piped())
+                .spawn()?;
+            let count = tar_unpack(child.stdout.take().unwrap(), to, exts)?;
+
+            if !child.wait()?.success() {
+                return Err(io::Error::other(format!("{} failed", decompressor)));
+            }
+            Ok(count)
+        }
+        None => Err(io::Error::other("not an archive")),
+    }
+}
+
+/// Writes the files of the [path] ZIP or plain tar archive (see is_random()),
+/// having the [exts] extensions, under [to], as unpack() does; hands each one
+/// to [each], as soon as it is written, along with its entry: [each] may do
+/// away with the file, before the next one is written.
+/// Returns the number of the files written.
+///
+pub fn unpack_each(
+    path: &Path,
+    to: &Path,
+    exts: &[&str],
+    each: impl FnMut(&Path, Entry) -> io::Result<()>,
+) -> io::Result<u64> {
+    fs::create_dir_all(to)?;
+
+    match kind(path) {
+        Some(Kind::Zip) => zip_unpack(path, to, exts, each),
+        Some(Kind::Tar(None)) => tar_unpack_each(fs::File::open(path)?, to, exts, each),
+        _ => Err(io::Error::other("no random access archive")),
+    }
+}
+
+/// Writes the [entry] file of the [path] archive (see unpack_each()) to [dst],
+/// straight from the archive.
+///
+pub fn entry_copy(path: &Path, entry: &Entry, dst: &Path) -> io::Result<()> {
+    let mut out = fs::File::create(dst)?;
+
+    match entry.data {
+        Some((offset, len)) => {
+            let mut file = fs::File::open(path)?;
+
+            file.seek(SeekFrom::Start(offset))?;
+            data_write(&mut file, len, &mut out)
+        }
+        None => unzip_entry(path, &entry.name, &mut out),
+    }
+}
+
+/// Returns the files of the [path] archive, having the [exts] extensions:
+/// (name, size); nothing is written to disk.
+///
+pub fn list(path: &Path, exts: &[&str]) -> io::Result<Vec<(String, u64)>> {
+    match kind(path) {
+        Some(Kind::Zip) => Ok(zip_entries(path)?
+            .into_iter()
+            .filter(|e| is_wanted(&e.name, exts))
+            .map(|e| (e.name, e.len))
+            .collect()),
+        Some(Kind::SevenZip) => seven_zip_list(path, exts),
+        Some(Kind::Tar(None)) => tar_list(fs::File::open(path)?, exts),
+        Some(Kind::Tar(Some(decompressor))) => {
+            let mut child = process::Command::new(decompressor)
+                .arg("-dc")
+                .arg(path)
+                .stdin(process::Stdio::null())
+                .stdout(process::Stdio::piped())
+                .spawn()?;
+            let list = tar_list(child.stdout.take().unwrap(), exts)?;
+
+            if !child.wait()?.success() {
+                return Err(io::Error::other(format!("{} failed", decompressor)));
+            }
+            Ok(list)
+        }
+        None => Err(io::Error::other("not an archive")),
+    }
+}
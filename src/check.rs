@@ -0,0 +1,216 @@
+use crate::{id3, sniff};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    fs,
+    hash::Hasher,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+/// Tracks shorter than that are suspiciously short.
+pub const SHORT_SECONDS: u32 = 5;
+
+/// What is known about a track of the album being checked.
+///
+pub struct Info {
+    pub path: PathBuf,
+    pub track: Option<u32>,
+    pub artist: String,
+    pub album: String,
+    pub format: String,
+    pub bitrate: u32,     // kbps.
+    pub cbr: bool,        // Constant bitrate, that is.
+    pub sample_rate: u32, // Hz.
+    pub seconds: u32,
+    pub hash: Option<u64>,
+}
+
+/// Returns true, if the [path] file is a constant bitrate MP3: no Xing, or VBRI
+/// header in its first frame. The bitrates of anything else vary by design.
+///
+pub fn is_cbr(path: &Path) -> io::Result<bool> {
+    if sniff::sniff(path)? != sniff::Container::Mpeg {
+        return Ok(false);
+    }
+    let (start, _) = id3::payload_range(path)?;
+    let mut file = fs::File::open(path)?;
+    let mut frame = vec![];
+
+    file.seek(SeekFrom::Start(start))?;
+    file.take(4 + 32 + 4).read_to_end(&mut frame)?; // Header, side info, tag.
+    Ok(!frame.windows(4).any(|w| w == b"Xing" || w == b"VBRI"))
+}
+
+/// Returns the hash of the audio payload of the [path] file, so that
+/// the same audio under different tags hashes the same: the MD5 signature
+/// from STREAMINFO for FLAC, the bytes between the tags for MP3,
+/// the whole file otherwise.
+///
+pub fn payload_hash(path: &Path) -> io::Result<u64> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = DefaultHasher::new();
+    let (start, end) = match sniff::sniff(path)? {
+        sniff::Container::Flac => {
+            let (mut head, mut md5) = ([0_u8; 10], [0_u8; 16]);
+            file.read_exact(&mut head)?;
+            let skip = id3::head_size(&head); // Ripped FLACs often start with ID3v2.
+            file.seek(SeekFrom::Start(skip + 8 + 18))?;
+            file.read_exact(&mut md5)?;
+            if md5.iter().any(|b| *b != 0) {
+                hasher.write(&md5);
+                return Ok(hasher.finish());
+            }
+            (0, file.metadata()?.len()) // Unset by the encoder.
+        }
+        sniff::Container::Mpeg => id3::payload_range(path)?,
+        _ => (0, file.metadata()?.len()),
+    };
+    let mut buf = vec![0; 1 << 16];
+
+    file.seek(SeekFrom::Start(start))?;
+    let mut reader = file.take(end - start);
+    loop {
+        match reader.read(&mut buf)? {
+            0 => break,
+            n => hasher.write(&buf[..n]),
+        }
+    }
+    Ok(hasher.finish())
+}
+
+/// Returns the problems with the track [numbers] of a directory (disc),
+/// in the natural order of files: missing numbers, gaps, duplicates,
+/// and numbers out of order.
+///
+pub fn numbering_problems(numbers: &[Option<u32>]) -> Vec<String> {
+    let mut problems = vec![];
+    let missing = numbers.iter().filter(|n| n.is_none()).count();
+    let mut seen: BTreeMap<u32, usize> = BTreeMap::new();
+
+    if missing > 0 {
+        problems.push(format!("{} track(s) without track number", missing));
+    }
+    for n in numbers.iter().flatten() {
+        *seen.entry(*n).or_default() += 1;
+    }
+    let dups: Vec<String> = seen
+        .iter()
+        .filter(|(_, c)| **c > 1)
+        .map(|(n, c)| format!("{} (x{})", n, c))
+        .collect();
+    if !dups.is_empty() {
+        problems.push(format!("duplicate track numbers: {}", dups.join(", ")));
+    }
+    if let Some(max) = seen.keys().next_back() {
+        let gaps: Vec<String> = (1..*max)
+            .filter(|n| !seen.contains_key(n))
+            .map(|n| n.to_string())
+            .collect();
+        if !gaps.is_empty() {
+            problems.push(format!("gaps in track numbers: {}", gaps.join(", ")));
+        }
+    }
+    let present: Vec<u32> = numbers.iter().flatten().copied().collect();
+    let disorder = present.windows(2).filter(|w| w[1] < w[0]).count();
+    if disorder > 0 {
+        problems.push(format!(
+            "file order and tag order disagree at {} place(s)",
+            disorder
+        ));
+    }
+    problems
+}
+
+/// Returns a description of the distinct [values] with their counts,
+/// if there is more than one of them; None otherwise.
+///
+pub fn mixture<T: Ord + ToString>(values: impl Iterator<Item = T>) -> Option<String> {
+    let mut counts: BTreeMap<T, usize> = BTreeMap::new();
+
+    for v in values {
+        *counts.entry(v).or_default() += 1;
+    }
+    if counts.len() < 2 {
+        return None;
+    }
+    Some(
+        counts
+            .iter()
+            .map(|(v, c)| format!("{} (x{})", v.to_string(), c))
+            .collect::<Vec<_>>()
+            .join(", "),
+    )
+}
+
+/// Returns all the problems found with the album [infos], in the natural order.
+///
+pub fn problems(infos: &[Info]) -> Vec<String> {
+    let mut problems = vec![];
+    let mut discs: BTreeMap<&Path, Vec<Option<u32>>> = BTreeMap::new();
+
+    for info in infos {
+        discs
+            .entry(
+                info.path
+                    .parent()
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .unwrap_or(Path::new(".")),
+            )
+            .or_default()
+            .push(info.track);
+    }
+    for (dir, numbers) in &discs {
+        for p in numbering_problems(numbers) {
+            problems.push(format!("\"{}\": {}", dir.display(), p));
+        }
+    }
+
+    let quoted = |s: &String| format!("\"{}\"", s);
+    if let Some(m) = mixture(infos.iter().map(|i| i.format.clone())) {
+        problems.push(format!("mixed formats: {}", m));
+    }
+    if let Some(m) = mixture(infos.iter().map(|i| i.sample_rate)) {
+        problems.push(format!("mixed sample rates, Hz: {}", m));
+    }
+    if let Some(m) = mixture(infos.iter().filter(|i| i.cbr).map(|i| i.bitrate)) {
+        problems.push(format!("mixed bitrates, kbps: {}", m));
+    }
+    if let Some(m) = mixture(infos.iter().map(|i| quoted(&i.artist))) {
+        problems.push(format!("inconsistent artist tags: {}", m));
+    }
+    if let Some(m) = mixture(infos.iter().map(|i| quoted(&i.album))) {
+        problems.push(format!("inconsistent album tags: {}", m));
+    }
+
+    for info in infos {
+        if info.seconds == 0 {
+            problems.push(format!("\"{}\": empty track", info.path.display()));
+        } else if info.seconds < SHORT_SECONDS {
+            problems.push(format!(
+                "\"{}\": very short track, {}s",
+                info.path.display(),
+                info.seconds
+            ));
+        }
+    }
+
+    let mut same: HashMap<u64, Vec<&Path>> = HashMap::new();
+    for info in infos {
+        if let Some(hash) = info.hash {
+            same.entry(hash).or_default().push(&info.path);
+        }
+    }
+    let mut dups: Vec<&Vec<&Path>> = same.values().filter(|v| v.len() > 1).collect();
+    dups.sort();
+    for paths in dups {
+        problems.push(format!(
+            "same audio: {}",
+            paths
+                .iter()
+                .map(|p| format!("\"{}\"", p.display()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    problems
+}
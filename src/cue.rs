@@ -0,0 +1,412 @@
+use crate::id3;
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+    process,
+};
+
+/// CUE sheet positions are in CD frames, 75 per second.
+pub const CD_FRAMES: u64 = 75;
+
+/// A track of a CUE sheet; [start] is its INDEX 01 position, CD frames.
+///
+#[derive(Debug, PartialEq)]
+pub struct Track {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub start: u64,
+}
+
+/// An audio file of a CUE sheet, with its tracks.
+///
+#[derive(Debug, PartialEq)]
+pub struct File {
+    pub name: String,
+    pub tracks: Vec<Track>,
+}
+
+/// A parsed CUE sheet; album TITLE and PERFORMER, if any.
+///
+#[derive(Debug, Default, PartialEq)]
+pub struct Sheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub files: Vec<File>,
+}
+
+/// Returns the [bytes] of a CUE sheet, decoded: UTF-8, if valid,
+/// otherwise Latin-1, which never fails.
+///
+pub fn decode(bytes: &[u8]) -> String {
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes.iter().map(|b| *b as char).collect(),
+    }
+}
+
+/// Splits the [rest] of a CUE line into its first value (quoted or not)
+/// and the remainder.
+///
+fn value_split(rest: &str) -> (String, &str) {
+    let rest = rest.trim_start();
+
+    if let Some(quoted) = rest.strip_prefix('"') {
+        match quoted.find('"') {
+            Some(end) => (quoted[..end].to_string(), &quoted[end + 1..]),
+            None => (quoted.to_string(), ""),
+        }
+    } else {
+        match rest.find(char::is_whitespace) {
+            Some(end) => (rest[..end].to_string(), &rest[end..]),
+            None => (rest.to_string(), ""),
+        }
+    }
+}
+
+/// Returns the mm:ss:ff [position] in CD frames.
+///
+fn position_parse(position: &str) -> Option<u64> {
+    let parts: Vec<u64> = position
+        .split(':')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+
+    match parts[..] {
+        [m, s, f] if s < 60 && f < CD_FRAMES => Some((m * 60 + s) * CD_FRAMES + f),
+        _ => None,
+    }
+}
+
+/// Parses the [text] of a CUE sheet; the error tells the line at fault.
+///
+pub fn parse(text: &str) -> Result<Sheet, String> {
+    let mut sheet = Sheet::default();
+
+    for (n, line) in text.lines().enumerate() {
+        let fail = |what: &str| format!("line {}: {}", n + 1, what);
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let (value, rest) = value_split(rest);
+
+        match command.to_uppercase().as_str() {
+            "FILE" => sheet.files.push(File {
+                name: value,
+                tracks: vec![],
+            }),
+            "TRACK" => {
+                let file = sheet
+                    .files
+                    .last_mut()
+                    .ok_or_else(|| fail("TRACK before FILE"))?;
+                file.tracks.push(Track {
+                    number: value.parse().map_err(|_| fail("bad track number"))?,
+                    title: None,
+                    performer: None,
+                    start: 0,
+                });
+            }
+            "INDEX" if value == "01" => {
+                let track = sheet
+                    .files
+                    .last_mut()
+                    .and_then(|f| f.tracks.last_mut())
+                    .ok_or_else(|| fail("INDEX before TRACK"))?;
+                track.start = position_parse(value_split(rest).0.as_str())
+                    .ok_or_else(|| fail("bad INDEX"))?;
+            }
+            "TITLE" | "PERFORMER" => {
+                let track = sheet.files.last_mut().and_then(|f| f.tracks.last_mut());
+                let field = match (command.to_uppercase().as_str(), track) {
+                    ("TITLE", Some(track)) => &mut track.title,
+                    ("TITLE", None) => &mut sheet.title,
+                    (_, Some(track)) => &mut track.performer,
+                    (_, None) => &mut sheet.performer,
+                };
+                *field = Some(value).filter(|v| !v.is_empty());
+            }
+            _ => (), // REM, INDEX 00, FLAGS, ISRC, and such.
+        }
+    }
+    if sheet.files.iter().all(|f| f.tracks.is_empty()) {
+        return Err("no tracks".to_string());
+    }
+    Ok(sheet)
+}
+
+/// Returns the [start] of a track (CD frames) in samples at [rate].
+///
+pub fn samples_at(start: u64, rate: u32) -> u64 {
+    start * rate as u64 / CD_FRAMES
+}
+
+fn copy_range(src: &mut fs::File, dst: &mut fs::File, start: u64, end: u64) -> io::Result<()> {
+    src.seek(SeekFrom::Start(start))?;
+    io::copy(&mut src.take(end - start), dst)?;
+    Ok(())
+}
+
+/// Returns (frame length, samples per frame, sample rate) of the MPEG audio
+/// frame [header], if valid.
+///
+pub fn mpeg_frame(header: &[u8]) -> Option<(u64, u64, u32)> {
+    // kbps: V1 L1, V1 L2, V1 L3, V2 L1, V2 L2 and L3.
+    #[rustfmt::skip]
+    const BITRATES: [[u32; 15]; 5] = [
+        [0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448],
+        [0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384],
+        [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320],
+        [0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256],
+        [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+    ];
+    const RATES: [u32; 3] = [44100, 48000, 32000];
+
+    if header.len() < 4 || header[0] != 0xff || header[1] & 0xe0 != 0xe0 {
+        return None;
+    }
+    let version = (header[1] >> 3) & 0x03; // 0: 2.5, 2: 2, 3: 1.
+    let layer = 4 - ((header[1] >> 1) & 0x03); // 1, 2, 3; 4 is invalid.
+    let bitrate_ii = (header[2] >> 4) as usize;
+    let rate_ii = ((header[2] >> 2) & 0x03) as usize;
+    let padding = ((header[2] >> 1) & 0x01) as u64;
+
+    if version == 1 || layer == 4 || bitrate_ii == 0 || bitrate_ii == 15 || rate_ii == 3 {
+        return None;
+    }
+    let v1 = version == 3;
+    let table = match (v1, layer) {
+        (true, l) => l as usize - 1,
+        (false, 1) => 3,
+        (false, _) => 4,
+    };
+    let bitrate = BITRATES[table][bitrate_ii] as u64 * 1000;
+    let rate = RATES[rate_ii] >> (3 - version).min(2);
+    let samples: u64 = match (layer, v1) {
+        (1, _) => 384,
+        (3, false) => 576,
+        _ => 1152,
+    };
+    let len = if layer == 1 {
+        (12 * bitrate / rate as u64 + padding) * 4
+    } else {
+        samples / 8 * bitrate / rate as u64 + padding
+    };
+    Some((len, samples, rate))
+}
+
+/// Goes through the MPEG audio frames of the [path] file, a chunk at a time,
+/// handing the offset, header, samples, and sample rate of each one to [each].
+/// The Xing/Info frame, if any, is left out: it is no audio.
+/// Returns the offset, where the audio ends.
+///
+pub fn mpeg_scan(path: &Path, mut each: impl FnMut(u64, &[u8], u64, u32)) -> io::Result<u64> {
+    const FRAME_MAX: usize = 4096; // Longer than any frame; enough to tell Xing/Info.
+
+    let (start, end) = id3::payload_range(path)?;
+    let mut file = fs::File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut file = file.take(end - start);
+
+    let mut data = vec![]; // What is not gone through yet, from [base] on.
+    let mut chunk = vec![0; 1 << 16];
+    let mut base = start;
+    let mut at = 0;
+    let mut audio = false;
+    loop {
+        if data.len() < at + FRAME_MAX {
+            let gone = at.min(data.len()); // A cut last frame ends beyond.
+            data.drain(..gone);
+            base += gone as u64;
+            at -= gone;
+            let n = file.read(&mut chunk)?;
+            if n > 0 {
+                data.extend_from_slice(&chunk[..n]);
+                continue;
+            }
+        }
+        if at + 4 > data.len() {
+            break;
+        }
+        match mpeg_frame(&data[at..]) {
+            Some((len, samples, rate)) if len > 0 => {
+                let frame = &data[at..(at + len as usize).min(data.len())];
+                let is_info = !audio
+                    && frame
+                        .windows(4)
+                        .take(64)
+                        .any(|w| w == b"Xing" || w == b"Info");
+                if !is_info {
+                    each(base + at as u64, &frame[..4], samples, rate);
+                    audio = true;
+                }
+                at += len as usize;
+            }
+            _ => at += 1, // Garbage; resync.
+        }
+    }
+    Ok(end)
+}
+
+/// Returns the byte offsets of the MPEG audio frames of the [path] file,
+/// each with the sample it starts at, and the sample rate.
+///
+pub fn mpeg_frames(path: &Path) -> io::Result<(Vec<(u64, u64)>, u32)> {
+    let mut frames = vec![];
+    let mut rate = 0;
+    let mut sample = 0;
+    let end = mpeg_scan(path, |offset, _, samples, r| {
+        frames.push((offset, sample));
+        sample += samples;
+        rate = r;
+    })?;
+
+    frames.push((end, sample));
+    Ok((frames, rate))
+}
+
+/// Cuts the [path] MP3 file at the track [starts] (CD frames), at the
+/// frame boundaries nearest to them; writes the pieces to [dsts].
+///
+pub fn mp3_split(path: &Path, starts: &[u64], dsts: &[&Path]) -> io::Result<()> {
+    let (frames, rate) = mpeg_frames(path)?;
+    let mut file = fs::File::open(path)?;
+    let offset_at = |start: u64| {
+        let sample = samples_at(start, rate);
+        frames
+            .iter()
+            .min_by_key(|(_, s)| s.abs_diff(sample))
+            .map_or(0, |(offset, _)| *offset)
+    };
+
+    for (ii, dst) in dsts.iter().enumerate() {
+        let begin = offset_at(starts[ii]);
+        let end = starts
+            .get(ii + 1)
+            .map_or(frames.last().unwrap().0, |s| offset_at(*s));
+        copy_range(
+            &mut file,
+            &mut fs::File::create(dst)?,
+            begin,
+            end.max(begin),
+        )?;
+    }
+    Ok(())
+}
+
+/// Returns the (fmt chunk, data offset, data length) of the [file] RIFF WAVE.
+///
+fn wav_chunks(file: &mut fs::File) -> io::Result<(Vec<u8>, u64, u64)> {
+    let bad = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+    let len = file.metadata()?.len();
+    let mut head = [0_u8; 12];
+    let mut fmt = None;
+    let mut at = 12;
+
+    file.read_exact(&mut head)?;
+    if &head[..4] != b"RIFF" || &head[8..] != b"WAVE" {
+        return Err(bad("not a RIFF WAVE"));
+    }
+    while at + 8 <= len {
+        let mut chunk = [0_u8; 8];
+        file.seek(SeekFrom::Start(at))?;
+        file.read_exact(&mut chunk)?;
+        let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as u64;
+        match &chunk[..4] {
+            b"fmt " => {
+                let mut body = vec![0; size as usize];
+                file.read_exact(&mut body)?;
+                fmt = Some(body);
+            }
+            b"data" => {
+                let fmt = fmt.ok_or_else(|| bad("data before fmt"))?;
+                return Ok((fmt, at + 8, size.min(len - at - 8)));
+            }
+            _ => (),
+        }
+        at += 8 + size + size % 2;
+    }
+    Err(bad("no data chunk"))
+}
+
+/// Cuts the [path] WAV file at the track [starts] (CD frames), exactly
+/// to the sample; writes the pieces to [dsts].
+///
+pub fn wav_split(path: &Path, starts: &[u64], dsts: &[&Path]) -> io::Result<()> {
+    let mut file = fs::File::open(path)?;
+    let (fmt, data, data_len) = wav_chunks(&mut file)?;
+
+    if fmt.len() < 16 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "short fmt chunk",
+        ));
+    }
+    let rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+    let align = u16::from_le_bytes([fmt[12], fmt[13]]) as u64;
+    let offset_at = |start: u64| (samples_at(start, rate) * align).min(data_len);
+
+    for (ii, dst) in dsts.iter().enumerate() {
+        let begin = offset_at(starts[ii]);
+        let end = starts.get(ii + 1).map_or(data_len, |s| offset_at(*s));
+        let size = end.saturating_sub(begin);
+        let mut out = fs::File::create(dst)?;
+
+        out.write_all(b"RIFF")?;
+        out.write_all(&((4 + 8 + fmt.len() as u64 + 8 + size) as u32).to_le_bytes())?;
+        out.write_all(b"WAVEfmt ")?;
+        out.write_all(&(fmt.len() as u32).to_le_bytes())?;
+        out.write_all(&fmt)?;
+        out.write_all(b"data")?;
+        out.write_all(&(size as u32).to_le_bytes())?;
+        copy_range(&mut file, &mut out, data + begin, data + begin + size)?;
+    }
+    Ok(())
+}
+
+/// Cuts the [path] lossless file (FLAC, APE, and such) at the track [starts]
+/// (CD frames), exactly to the sample, [rate] being its sample rate, by ffmpeg;
+/// writes the pieces, FLAC encoded, to [dsts]. The file is decoded once,
+/// the audio split into as many trimmed outputs.
+///
+pub fn lossless_split(path: &Path, rate: u32, starts: &[u64], dsts: &[&Path]) -> io::Result<()> {
+    let mut graph = format!("[0:a:0]asplit={}", dsts.len());
+    let mut outputs: Vec<OsString> = vec![];
+
+    graph += &(0..dsts.len())
+        .map(|ii| format!("[a{}]", ii))
+        .collect::<String>();
+    for (ii, dst) in dsts.iter().enumerate() {
+        graph += &format!(
+            ";[a{0}]atrim=start_sample={1}",
+            ii,
+            samples_at(starts[ii], rate)
+        );
+        if let Some(next) = starts.get(ii + 1) {
+            graph += &format!(":end_sample={}", samples_at(*next, rate));
+        }
+        graph += &format!(",asetpts=PTS-STARTPTS[t{}]", ii);
+        outputs.extend([
+            "-map".into(),
+            format!("[t{}]", ii).into(),
+            "-c:a".into(),
+            "flac".into(),
+            dst.as_os_str().to_os_string(),
+        ]);
+    }
+    let status = process::Command::new("ffmpeg")
+        .args(["-v", "error", "-nostdin", "-y", "-i"])
+        .arg(path)
+        .arg("-filter_complex")
+        .arg(graph)
+        .args(outputs)
+        .status()?;
+
+    if !status.success() {
+        return Err(io::Error::other(format!("ffmpeg failed: {}", status)));
+    }
+    Ok(())
+}
@@ -0,0 +1,221 @@
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// An ID3v2 frame, kept verbatim.
+///
+pub struct Frame {
+    pub id: [u8; 4],
+    flags: [u8; 2],
+    data: Vec<u8>,
+}
+
+fn syncsafe(b: &[u8]) -> u64 {
+    b.iter().fold(0, |acc, &x| (acc << 7) | (x & 0x7f) as u64)
+}
+
+fn syncsafe_bytes(n: usize) -> [u8; 4] {
+    [
+        (n >> 21 & 0x7f) as u8,
+        (n >> 14 & 0x7f) as u8,
+        (n >> 7 & 0x7f) as u8,
+        (n & 0x7f) as u8,
+    ]
+}
+
+/// Returns the size of the ID3v2 tag, [head] being the first 10 bytes of a file;
+/// 0, if there is no tag.
+///
+pub fn head_size(head: &[u8]) -> u64 {
+    if head.len() < 10 || &head[..3] != b"ID3" || head[6..10].iter().any(|b| b & 0x80 != 0) {
+        return 0;
+    }
+    let footer = if head[3] == 4 && head[5] & 0x10 != 0 {
+        10
+    } else {
+        0
+    };
+    10 + syncsafe(&head[6..10]) + footer
+}
+
+/// Returns the size of the ID3v1 tag at the end of [file], [len] bytes long;
+/// 0, if there is none.
+///
+pub fn v1_size(file: &mut fs::File, len: u64) -> io::Result<u64> {
+    let mut buf = [0_u8; 3];
+
+    if len >= 128 {
+        file.seek(SeekFrom::Start(len - 128))?;
+        file.read_exact(&mut buf)?;
+        if &buf == b"TAG" {
+            return Ok(128);
+        }
+    }
+    Ok(0)
+}
+
+/// Returns the size of the ID3v1 and APEv2 tags at the end of [file], [len] bytes long.
+///
+pub fn tail_size(file: &mut fs::File, len: u64) -> io::Result<u64> {
+    let mut tail = v1_size(file, len)?;
+    let mut buf = [0_u8; 32];
+
+    if len >= tail + 32 {
+        file.seek(SeekFrom::Start(len - tail - 32))?;
+        file.read_exact(&mut buf)?;
+        if &buf[..8] == b"APETAGEX" {
+            let size = u32::from_le_bytes([buf[12], buf[13], buf[14], buf[15]]) as u64;
+            let header = if buf[23] & 0x80 != 0 { 32 } else { 0 };
+            if len >= tail + size + header {
+                tail += size + header;
+            }
+        }
+    }
+    Ok(tail)
+}
+
+/// Returns the (start, end) byte range of the audio payload of the [path] MP3 file,
+/// excluding the tags at both ends.
+///
+pub fn payload_range(path: &Path) -> io::Result<(u64, u64)> {
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut head = [0_u8; 10];
+    let start = match file.read_exact(&mut head) {
+        Ok(_) => head_size(&head).min(len),
+        Err(_) => 0,
+    };
+    let end = len - tail_size(&mut file, len)?;
+
+    Ok((start, end.max(start)))
+}
+
+/// Parses the frames of [tag], the complete ID3v2 tag. Returns the major version,
+/// and the frames; None for anything but plain v2.3, or v2.4 tags.
+///
+pub fn frames_parse(tag: &[u8]) -> Option<(u8, Vec<Frame>)> {
+    let major = tag[3];
+    if !(3..=4).contains(&major) || tag[5] & 0xc0 != 0 {
+        return None; // Unsynchronisation, or extended header.
+    }
+    let end = (10 + syncsafe(&tag[6..10]) as usize).min(tag.len());
+    let mut frames = vec![];
+    let mut pos = 10;
+
+    while pos + 10 <= end && tag[pos] != 0 {
+        let id: [u8; 4] = tag[pos..pos + 4].try_into().unwrap();
+        if !id
+            .iter()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+        {
+            return None;
+        }
+        let size = if major == 4 {
+            syncsafe(&tag[pos + 4..pos + 8])
+        } else {
+            u32::from_be_bytes(tag[pos + 4..pos + 8].try_into().unwrap()) as u64
+        } as usize;
+        if pos + 10 + size > end {
+            return None;
+        }
+        frames.push(Frame {
+            id,
+            flags: [tag[pos + 8], tag[pos + 9]],
+            data: tag[pos + 10..pos + 10 + size].to_vec(),
+        });
+        pos += 10 + size;
+    }
+    Some((major, frames))
+}
+
+/// Makes a text frame of [id] for the [major] version tag: UTF-8 for v2.4,
+/// UTF-16 for v2.3.
+///
+pub fn text_frame(major: u8, id: &str, text: &str) -> Frame {
+    let data = if major == 4 {
+        [&[3_u8][..], text.as_bytes()].concat()
+    } else {
+        [1_u8, 0xff, 0xfe]
+            .into_iter()
+            .chain(text.encode_utf16().flat_map(|u| u.to_le_bytes()))
+            .collect()
+    };
+    Frame {
+        id: id.as_bytes().try_into().unwrap(),
+        flags: [0, 0],
+        data,
+    }
+}
+
+/// Makes a user defined text frame (TXXX) of [description] for the [major]
+/// version tag.
+///
+pub fn user_text_frame(major: u8, description: &str, text: &str) -> Frame {
+    let mut frame = text_frame(major, "TXXX", &format!("{}\0{}", description, text));
+
+    if major != 4 {
+        // Both strings of a UTF-16 frame start with a BOM.
+        let at = 3 + 2 * description.encode_utf16().count() + 2;
+        frame.data.splice(at..at, [0xff, 0xfe]);
+    }
+    frame
+}
+
+impl Frame {
+    /// Returns the description of a user defined text frame (TXXX), if it is one.
+    ///
+    pub fn user_text_description(&self) -> Option<String> {
+        if &self.id != b"TXXX" || self.data.is_empty() {
+            return None;
+        }
+        let body = &self.data[1..];
+        match self.data[0] {
+            1 | 2 => {
+                let units: Vec<u16> = body
+                    .chunks_exact(2)
+                    .map(|u| u16::from_le_bytes([u[0], u[1]]))
+                    .take_while(|u| *u != 0)
+                    .collect();
+                let big_endian = units.first() == Some(&0xfffe) || self.data[0] == 2;
+                let units = units
+                    .into_iter()
+                    .map(|u| if big_endian { u.swap_bytes() } else { u });
+                Some(String::from_utf16_lossy(
+                    &units.filter(|u| *u != 0xfeff).collect::<Vec<_>>(),
+                ))
+            }
+            _ => Some(
+                String::from_utf8_lossy(
+                    &body[..body.iter().position(|b| *b == 0).unwrap_or(body.len())],
+                )
+                .to_string(),
+            ),
+        }
+    }
+}
+
+/// Returns the complete [major] version ID3v2 tag of [frames].
+///
+pub fn tag_build(major: u8, frames: &[Frame]) -> Vec<u8> {
+    let body: Vec<u8> = frames
+        .iter()
+        .flat_map(|f| {
+            let size = if major == 4 {
+                syncsafe_bytes(f.data.len())
+            } else {
+                (f.data.len() as u32).to_be_bytes()
+            };
+            [&f.id[..], &size, &f.flags, &f.data].concat()
+        })
+        .collect();
+
+    [
+        &b"ID3"[..],
+        &[major, 0, 0],
+        &syncsafe_bytes(body.len()),
+        &body,
+    ]
+    .concat()
+}
@@ -0,0 +1,177 @@
+use crate::trash;
+use chrono::Local;
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::ffi::{OsStrExt, OsStringExt},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+lazy_static! {
+    static ref JOURNAL: Mutex<Option<fs::File>> = Mutex::new(None);
+}
+
+/// Something a run did to the file system.
+///
+pub enum Entry<'a> {
+    Dir(&'a Path),               // Directory created.
+    File(&'a Path),              // File created.
+    Moved(&'a Path, &'a Path),   // Moved from, to.
+    Trashed(&'a Path, &'a Path), // Moved to the trash from, to.
+    Removed(&'a Path),           // Gone for good.
+}
+
+/// Returns the default journal path for the [name] destination,
+/// in the XDG state directory.
+///
+pub fn default_path(name: &str) -> PathBuf {
+    env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/state")))
+        .unwrap_or_else(env::temp_dir)
+        .join("procrustes")
+        .join(format!(
+            "{} {}.journal",
+            Local::now().format("%Y%m%d-%H%M%S"),
+            name
+        ))
+}
+
+/// Starts journaling to [path]; until then, record() does nothing.
+///
+pub fn open(path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    *JOURNAL.lock().unwrap() = Some(fs::File::create(path)?);
+    Ok(())
+}
+
+/// Returns [path] as a journal field: tabs, line breaks, backslashes,
+/// and the bytes, which are not UTF-8, escaped.
+///
+pub fn field_escape(path: &Path) -> String {
+    let bytes = path.as_os_str().as_bytes();
+    let mut field = String::with_capacity(bytes.len());
+
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\\' => field.push_str("\\\\"),
+                '\t' => field.push_str("\\t"),
+                '\n' => field.push_str("\\n"),
+                '\r' => field.push_str("\\r"),
+                c => field.push(c),
+            }
+        }
+        for b in chunk.invalid() {
+            field.push_str(&format!("\\x{:02x}", b));
+        }
+    }
+    field
+}
+
+/// Returns the path, escaped by field_escape() as [field]; None, if garbled.
+///
+pub fn field_unescape(field: &str) -> Option<PathBuf> {
+    let mut bytes = Vec::with_capacity(field.len());
+    let mut chars = field.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            continue;
+        }
+        match chars.next()? {
+            '\\' => bytes.push(b'\\'),
+            't' => bytes.push(b'\t'),
+            'n' => bytes.push(b'\n'),
+            'r' => bytes.push(b'\r'),
+            'x' => {
+                let hex: String = chars.by_ref().take(2).collect();
+                bytes.push(
+                    u8::from_str_radix(&hex, 16)
+                        .ok()
+                        .filter(|_| hex.len() == 2)?,
+                );
+            }
+            _ => return None,
+        }
+    }
+    Some(PathBuf::from(OsString::from_vec(bytes)))
+}
+
+/// Writes [entry] to the journal, if it is open. Every entry is flushed
+/// at once, so that an interrupted run can be undone, too.
+///
+pub fn record(entry: Entry) {
+    if let Some(file) = JOURNAL.lock().unwrap().as_mut() {
+        let line = match entry {
+            Entry::Dir(p) => format!("D\t{}", field_escape(p)),
+            Entry::File(p) => format!("F\t{}", field_escape(p)),
+            Entry::Moved(from, to) => format!("M\t{}\t{}", field_escape(from), field_escape(to)),
+            Entry::Trashed(from, to) => {
+                format!("T\t{}\t{}", field_escape(from), field_escape(to))
+            }
+            Entry::Removed(p) => format!("R\t{}", field_escape(p)),
+        };
+        writeln!(file, "{}", line)
+            .and_then(|_| file.flush())
+            .expect("Failed to write the journal!");
+    }
+}
+
+/// Undoes the run recorded in the [path] journal, last things first: removes
+/// the created files and (empty) directories, moves the moved things back,
+/// the trashed ones, too, along with their trash info.
+/// Returns the count of entries undone, and the complaints about the rest.
+///
+pub fn undo(path: &Path) -> io::Result<(u64, Vec<String>)> {
+    let lines: Vec<String> = BufReader::new(fs::File::open(path)?)
+        .lines()
+        .collect::<Result<_, _>>()?;
+    let mut undone = 0;
+    let mut complaints = vec![];
+
+    for line in lines.iter().rev() {
+        let mut fields = line.split('\t');
+        let kind = fields.next().unwrap_or_default();
+        let paths: Option<Vec<PathBuf>> = fields.map(field_unescape).collect();
+        let result = match (kind, paths.as_deref()) {
+            ("F", Some([p])) => fs::remove_file(p).map(|_| true),
+            ("D", Some([p])) => fs::remove_dir(p).map(|_| true),
+            ("M", Some([from, to])) | ("T", Some([from, to])) => {
+                if from.exists() {
+                    Err(io::Error::new(
+                        io::ErrorKind::AlreadyExists,
+                        format!("\"{}\" is in the way", from.display()),
+                    ))
+                } else {
+                    fs::rename(to, from).map(|_| {
+                        if kind == "T" {
+                            // No orphan entry left for the file managers.
+                            trash::info_path(to).map(fs::remove_file);
+                        }
+                        true
+                    })
+                }
+            }
+            ("R", Some([_])) => Ok(false),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "garbled entry")),
+        };
+        match result {
+            Ok(true) => undone += 1,
+            Ok(false) => complaints.push(format!(
+                "Removed for good: \"{}\"",
+                paths.unwrap()[0].display()
+            )),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (), // Already gone.
+            Err(e) => complaints.push(format!("{}: {}", line.replace('\t', " "), e)),
+        }
+    }
+    Ok((undone, complaints))
+}
@@ -0,0 +1,68 @@
+use chrono::Local;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::{
+    fs,
+    io::{self, Write},
+    panic,
+    path::Path,
+    sync::Mutex,
+};
+
+/// Writes log records, a line per record, to a file, or to stderr.
+///
+struct Logger {
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let mut out = self.out.lock().unwrap();
+            writeln!(
+                out,
+                "{} {:<5} {}",
+                Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                record.level(),
+                record.args()
+            )
+            .and_then(|_| out.flush())
+            .ok();
+        }
+    }
+
+    fn flush(&self) {
+        self.out.lock().unwrap().flush().ok();
+    }
+}
+
+/// Starts logging records up to [level] to the [path] file (appending),
+/// or to stderr, if None. Panics are logged, too, as errors.
+///
+pub fn init(path: Option<&Path>, level: LevelFilter) -> io::Result<()> {
+    let out: Box<dyn Write + Send> = match path {
+        Some(path) => Box::new(
+            fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?,
+        ),
+        None => Box::new(io::stderr()),
+    };
+
+    log::set_boxed_logger(Box::new(Logger {
+        out: Mutex::new(out),
+    }))
+    .map_err(|e| io::Error::other(e.to_string()))?;
+    log::set_max_level(level);
+
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        log::error!("{}", info);
+        default_hook(info);
+    }));
+    Ok(())
+}
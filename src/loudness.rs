@@ -0,0 +1,241 @@
+use std::{
+    f64::consts::PI,
+    io::{self, Read},
+    path::Path,
+    process, thread,
+};
+
+/// The sample rate the audio is decoded at for measuring.
+///
+pub const RATE: u32 = 48000;
+
+/// A second order IIR filter section.
+///
+#[derive(Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    z: [f64; 2],
+}
+
+impl Biquad {
+    fn run(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.z[0];
+        self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+        self.z[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+/// Returns the K-weighting filter of ITU-R BS.1770 for the [rate]: the high shelf,
+/// then the high pass.
+///
+fn k_filter(rate: u32) -> [Biquad; 2] {
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (PI * f0 / rate as f64).tan();
+    let vh = 10_f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad {
+        b: [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (PI * f0 / rate as f64).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad {
+        b: [1.0, -2.0, 1.0],
+        a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        z: [0.0; 2],
+    };
+    [shelf, high_pass]
+}
+
+/// The loudness measurements of a track: the mean square energies of its 400 ms
+/// gating blocks (overlapping by 75%), and its sample peak.
+///
+#[derive(Clone, Debug, Default)]
+pub struct Loudness {
+    pub blocks: Vec<f64>,
+    pub peak: f32,
+}
+
+/// Measures the loudness of interleaved samples as EBU R128 does.
+///
+pub struct Meter {
+    channels: usize,
+    filters: Vec<[Biquad; 2]>,
+    weights: Vec<f64>,
+    step: usize,     // Frames per 100 ms.
+    count: usize,    // Frames in the current 100 ms.
+    sums: Vec<f64>,  // Of the squares in the current 100 ms, per channel.
+    parts: Vec<f64>, // Energies of the 100 ms parts so far.
+    peak: f32,
+}
+
+impl Meter {
+    pub fn new(rate: u32, channels: usize) -> Self {
+        Self {
+            channels,
+            filters: vec![k_filter(rate); channels],
+            // The LFE channel of 5.1 does not count, the surround ones count more.
+            weights: if channels == 6 {
+                vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41]
+            } else {
+                vec![1.0; channels]
+            },
+            step: rate as usize / 10,
+            count: 0,
+            sums: vec![0.0; channels],
+            parts: vec![],
+            peak: 0.0,
+        }
+    }
+
+    /// Feeds the meter with whole frames of [samples].
+    ///
+    pub fn add(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            for (c, &x) in frame.iter().enumerate() {
+                let [shelf, high_pass] = &mut self.filters[c];
+                let y = high_pass.run(shelf.run(x as f64));
+
+                self.sums[c] += y * y;
+                self.peak = self.peak.max(x.abs());
+            }
+            self.count += 1;
+            if self.count == self.step {
+                let energy = self
+                    .sums
+                    .iter()
+                    .zip(&self.weights)
+                    .map(|(s, w)| s * w)
+                    .sum::<f64>();
+
+                self.parts.push(energy / self.step as f64);
+                self.sums.fill(0.0);
+                self.count = 0;
+            }
+        }
+    }
+
+    pub fn finish(self) -> Loudness {
+        Loudness {
+            blocks: self
+                .parts
+                .windows(4)
+                .map(|w| w.iter().sum::<f64>() / 4.0)
+                .collect(),
+            peak: self.peak,
+        }
+    }
+}
+
+fn lufs(energy: f64) -> f64 {
+    -0.691 + 10.0 * energy.log10()
+}
+
+/// Returns the integrated loudness (LUFS) of the gating [blocks], gated absolutely
+/// at -70 LUFS, and relatively at 10 LU below; None for silence.
+///
+pub fn integrated<'a>(blocks: impl Iterator<Item = &'a f64>) -> Option<f64> {
+    let mean = |blocks: &[f64]| blocks.iter().sum::<f64>() / blocks.len() as f64;
+    let loud: Vec<f64> = blocks.cloned().filter(|e| lufs(*e) > -70.0).collect();
+
+    if loud.is_empty() {
+        return None;
+    }
+    let gate = lufs(mean(&loud)) - 10.0;
+    let gated: Vec<f64> = loud.into_iter().filter(|e| lufs(*e) > gate).collect();
+    Some(lufs(mean(&gated)))
+}
+
+/// Decodes [path] by ffmpeg to [channels] channels of RATE, and measures it;
+/// the ffmpeg complaints, if it fails, make the error.
+///
+pub fn analyze(path: &Path, channels: usize) -> io::Result<Loudness> {
+    let mut child = process::Command::new("ffmpeg")
+        .args(["-v", "error", "-nostdin", "-i"])
+        .arg(path)
+        .args(["-map", "0:a:0", "-f", "f32le", "-c:a", "pcm_f32le"])
+        .args(["-ac", &channels.to_string(), "-ar", &RATE.to_string(), "-"])
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped())
+        .spawn()?;
+    let mut out = child.stdout.take().unwrap();
+    let mut err = child.stderr.take().unwrap();
+    let errors = thread::spawn(move || {
+        let mut errors = String::new();
+        err.read_to_string(&mut errors).ok();
+        errors
+    });
+    let mut meter = Meter::new(RATE, channels);
+    let mut buf = vec![0_u8; 4 * channels * 4096];
+    let mut filled = 0;
+
+    loop {
+        let n = out.read(&mut buf[filled..])?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+        let whole = filled - filled % (4 * channels);
+        let samples: Vec<f32> = buf[..whole]
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+
+        meter.add(&samples);
+        buf.copy_within(whole..filled, 0);
+        filled -= whole;
+    }
+    let errors = errors.join().unwrap_or_default();
+    if !child.wait()?.success() {
+        return Err(io::Error::other(format!(
+            "ffmpeg failed: {}",
+            errors.trim()
+        )));
+    }
+    Ok(meter.finish())
+}
+
+/// Returns the gain tags of the [track], the [album] (all its tracks) measured:
+/// R128_* for Opus, REPLAYGAIN_* (ReplayGain 2.0, -18 LUFS) otherwise.
+///
+pub fn gain_tags(opus: bool, track: &Loudness, album: &[&Loudness]) -> Vec<(&'static str, String)> {
+    let album_blocks = album.iter().flat_map(|l| &l.blocks);
+    let (track_lufs, album_lufs) = match (integrated(track.blocks.iter()), integrated(album_blocks))
+    {
+        (Some(t), Some(a)) => (t, a),
+        _ => return vec![],
+    };
+
+    if opus {
+        let q78 = |lufs: f64| (((-23.0 - lufs) * 256.0).round() as i64).clamp(-32768, 32767);
+        vec![
+            ("R128_TRACK_GAIN", q78(track_lufs).to_string()),
+            ("R128_ALBUM_GAIN", q78(album_lufs).to_string()),
+        ]
+    } else {
+        let album_peak = album.iter().map(|l| l.peak).fold(0.0, f32::max);
+        vec![
+            (
+                "REPLAYGAIN_TRACK_GAIN",
+                format!("{:.2} dB", -18.0 - track_lufs),
+            ),
+            ("REPLAYGAIN_TRACK_PEAK", format!("{:.6}", track.peak)),
+            (
+                "REPLAYGAIN_ALBUM_GAIN",
+                format!("{:.2} dB", -18.0 - album_lufs),
+            ),
+            ("REPLAYGAIN_ALBUM_PEAK", format!("{:.6}", album_peak)),
+        ]
+    }
+}
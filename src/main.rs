@@ -1,23 +1,46 @@
 #[macro_use]
 extern crate lazy_static;
 
-mod spinner;
-use crate::spinner as spin;
-use crate::spinner::Spinner;
+mod archive;
+mod check;
+mod cue;
+mod id3;
+mod journal;
+mod logger;
+mod loudness;
+mod manifest;
+mod merge;
+mod meter;
+mod mp4;
+mod playlist;
+mod progress;
+mod sniff;
+mod tagprops;
+mod transcode;
+mod trash;
+use crate::progress::Progress;
 
 use alphanumeric_sort::sort_path_slice;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, Utc};
 use clap::{Arg, ArgMatches, Command};
 use itertools::join;
 use itertools::zip_eq;
 use regex::Regex;
 use std::{
-    cmp, env,
+    cmp,
+    collections::{BTreeMap, HashMap, HashSet},
+    env,
     ffi::OsStr,
     fs, io,
-    io::Write,
+    io::{IsTerminal, Read, Seek, SeekFrom, Write},
+    panic::{self, AssertUnwindSafe},
     path::{Path, PathBuf},
     process::exit,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Condvar, Mutex,
+    },
+    thread,
     time::Instant,
 };
 use tempfile::TempDir;
@@ -39,24 +62,39 @@ const APP_DESCRIPTION: &str = "Procrustes a.k.a. Damastes \
     \n\n<src> as a single file: \
     \n\nlibrary $ procrustes -va 'Vladimir Nabokov' -u 'Ada' ada.ogg .";
 
-const INVALID_ICON: &str = "\u{00274c}";
-const WARNING_ICON: &str = "\u{01f4a7}";
-const BDELIM_ICON: &str = "\u{01f539}";
-const ODELIM_ICON: &str = "\u{01f538}";
-const RSUSP_ICON: &str = "\u{002753}";
-const SUSPICIOUS_ICON: &str = "\u{002754}";
-const DONE_ICON: &str = "\u{01f7e2}";
-const COLUMN_ICON: &str = "\u{002714}";
 const LINK_ICON: &str = "\u{0026a1}";
-const START_ICON: &str = "\u{01f4a3}";
-const STOP_ICON: &str = "\u{01f4a5}";
 const USE_ICON: &str = "\u{002b50}";
+/// Room for the tags and the padding a copy gains, and for the file system slack,
+/// per track, when sizing volumes by the source files.
+const TRACK_HEADROOM: u64 = 64 * 1024;
+
+lazy_static! {
+    static ref SUSPICIOUS_FATE: &'static str = if flag("copy-suspicious") {
+        "copied untagged"
+    } else {
+        "skipped"
+    };
+    static ref ICONS_FANCY: bool = when_enabled(sval("icons"));
+    static ref COLOR: bool = when_enabled(sval("color")) && env::var_os("NO_COLOR").is_none();
+    static ref INVALID_ICON: &'static str = icon("\u{00274c}", "[x]");
+    static ref WARNING_ICON: &'static str = icon("\u{01f4a7}", "[!]");
+    static ref BDELIM_ICON: &'static str = icon("\u{01f539}", "#");
+    static ref ODELIM_ICON: &'static str = icon("\u{01f538}", "~");
+    static ref RSUSP_ICON: &'static str = icon("\u{002753}", "[?]");
+    static ref SUSPICIOUS_ICON: &'static str = icon("\u{002754}", "?");
+    static ref DONE_ICON: &'static str = icon("\u{01f7e2}", "[+]");
+    static ref COLUMN_ICON: &'static str = icon("\u{002714}", "|");
+    static ref START_ICON: &'static str = icon("\u{01f4a3}", ">>");
+    static ref STOP_ICON: &'static str = icon("\u{01f4a5}", "<<");
+}
 
 lazy_static! {
     static ref ARGS: ArgMatches = args_retrieve();
     static ref DST_DIR: PathBuf = dst_calculate();
     static ref KNOWN_EXTENSIONS: [&'static str; 9] =
         ["MP3", "OGG", "M4A", "M4B", "OPUS", "WMA", "FLAC", "APE", "WAV",];
+    static ref TRANSCODE_RULES: Vec<transcode::Rule> = transcode_rules();
+    static ref STREAMED: Mutex<HashMap<PathBuf, Streamed>> = Mutex::new(HashMap::new());
     static ref IS_ARTIST: bool = flag("a");
     static ref IS_UNIFIED: bool = flag("u");
     static ref UNIFIED: String = if *IS_UNIFIED {
@@ -116,17 +154,77 @@ fn pval(name: &str) -> PathBuf {
     Path::new(sval(name)).canonicalize().unwrap_or_else(|_| {
         panic!(
             "{}File or directory \"{}\" does not exist.{}",
-            BDELIM_ICON,
+            *BDELIM_ICON,
             sval(name),
-            BDELIM_ICON
+            *BDELIM_ICON
         )
     })
 }
 
+/// Returns the PathBuf values, associated with the [name] option, in order.
+/// Defined, if flag(name) is true.
+///
+fn pvals(name: &str) -> Vec<PathBuf> {
+    ARGS.values_of(name)
+        .unwrap()
+        .map(|v| {
+            Path::new(v).canonicalize().unwrap_or_else(|_| {
+                panic!(
+                    "{}File or directory \"{}\" does not exist.{}",
+                    *BDELIM_ICON, v, *BDELIM_ICON
+                )
+            })
+        })
+        .collect()
+}
+
+/// Returns true for [when] "always", false for "never"; otherwise,
+/// true, if stdout is a terminal.
+///
+fn when_enabled(when: &str) -> bool {
+    match when {
+        "always" => true,
+        "never" => false,
+        _ => io::stdout().is_terminal(),
+    }
+}
+
+/// Returns the [fancy] icon, or its [plain] ASCII equivalent, if icons are off.
+///
+fn icon(fancy: &'static str, plain: &'static str) -> &'static str {
+    if *ICONS_FANCY {
+        fancy
+    } else {
+        plain
+    }
+}
+
+/// Returns [text] in the [color] (ANSI SGR code), if colors are on.
+///
+fn paint(text: &str, color: u8) -> String {
+    if *COLOR {
+        format!("\x1b[{}m{}\x1b[0m", color, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Returns the help [text] of a really useful option, marked as such.
+///
+fn use_help(text: &str) -> &'static str {
+    Box::leak(format!("{} {}", USE_ICON, text).into_boxed_str())
+}
+
 /// Sets up command line parser, and gets the command line
 /// options and arguments.
 ///
 fn args_retrieve() -> ArgMatches {
+    args_command().get_matches()
+}
+
+/// Returns the command line parser.
+///
+fn args_command() -> Command<'static> {
     Command::new("procrustes")
         .version("v1.0.3")
         .author("")
@@ -135,7 +233,7 @@ fn args_retrieve() -> ArgMatches {
             Arg::new("v")
                 .short('v')
                 .long("verbose")
-                .help(&*format!("{} Verbose output", USE_ICON))
+                .help(use_help("Verbose output"))
         )
         .arg(
             Arg::new("d")
@@ -195,7 +293,7 @@ fn args_retrieve() -> ArgMatches {
             Arg::new("c")
                 .short('c')
                 .long("count")
-                .help("Just count the files"),
+                .help("Just count the files, their volume and duration"),
         )
         .arg(
             Arg::new("w")
@@ -203,6 +301,14 @@ fn args_retrieve() -> ArgMatches {
                 .long("overwrite")
                 .help("Silently remove existing destination directory (not recommended)"),
         )
+        .arg(
+            Arg::new("overwrite-mode")
+                .long("overwrite-mode")
+                .value_name("MODE")
+                .possible_values(["remove", "backup", "trash", "confirm"])
+                .help("Overwrite existing destination directory, disposing of it by MODE: remove, rename to timestamped backup, move to trash, or remove after confirmation")
+                .takes_value(true),
+        )
         .arg(
             Arg::new("y")
                 .short('y')
@@ -222,7 +328,7 @@ fn args_retrieve() -> ArgMatches {
                 .short('u')
                 .long("unified-name")
                 .value_name("UNAME")
-                .help(&*format!("{} UNAME for everything unspecified", USE_ICON))
+                .help(use_help("UNAME for everything unspecified"))
                 .takes_value(true),
         )
         .arg(
@@ -238,7 +344,7 @@ fn args_retrieve() -> ArgMatches {
                 .short('a')
                 .long("artist")
                 .value_name("ARTIST")
-                .help(&*format!("{} Artist tag", USE_ICON))
+                .help(use_help("Artist tag"))
                 .takes_value(true),
         )
         .arg(
@@ -246,13 +352,191 @@ fn args_retrieve() -> ArgMatches {
                 .short('m')
                 .long("album")
                 .value_name("ALBUM")
-                .help(&*format!("{} Album tag", USE_ICON))
+                .help(use_help("Album tag"))
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("sync")
+                .long("sync")
+                .conflicts_with_all(&["w", "p", "volume-roots"])
+                .help("Update the existing destination: copy new tracks, rename shifted ones, delete stale ones")
+        )
+        .arg(
+            Arg::new("max-per-dir")
+                .long("max-per-dir")
+                .value_name("N")
+                .conflicts_with("t")
+                .help("Put at most N files into a destination directory, chunking the flat output into numbered subdirectories")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("q")
+                .short('q')
+                .long("quiet")
+                .conflicts_with_all(&["v", "progress"])
+                .help("Report errors only"),
+        )
+        .arg(
+            Arg::new("icons")
+                .long("icons")
+                .value_name("WHEN")
+                .possible_values(["auto", "always", "never"])
+                .default_value("auto")
+                .help("Use emoji icons (auto: if stdout is a terminal), or plain ASCII")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .value_name("WHEN")
+                .possible_values(["auto", "always", "never"])
+                .default_value("auto")
+                .help("Color the output (auto: if stdout is a terminal, and NO_COLOR is not set)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("duplicates")
+                .long("duplicates")
+                .value_name("ACTION")
+                .possible_values(["report", "skip"])
+                .help("Find tracks with the same audio (tags aside), and report them, or skip all but the first one, keeping the numbering contiguous")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("playlist")
+                .long("playlist")
+                .value_name("FORMAT")
+                .possible_values(playlist::FORMATS)
+                .multiple_occurrences(true)
+                .help("Write a playlist of the copied tracks into the destination root (may be repeated for more formats)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("fix-ext")
+                .long("fix-ext")
+                .help("Give the destination files the extension, fitting their content, if it does not fit already; the ones, readable only so, get copied and tagged, too"),
+        )
+        .arg(
+            Arg::new("copy-suspicious")
+                .long("copy-suspicious")
+                .help("Copy suspicious files (known extension, unreadable content) untagged, rather than skip them"),
+        )
+        .arg(
+            Arg::new("ignore-cue")
+                .long("ignore-cue")
+                .help("Do not split single-file albums by their CUE sheets"),
+        )
+        .arg(
+            Arg::new("merge")
+                .long("merge")
+                .value_name("TARGET")
+                .help("Merge consecutive MP3 (of a constant bitrate) or FLAC tracks of the same stream parameters losslessly into longer ones, up to the TARGET duration (e.g. 30m, 1h30m), or a track per source directory (dir)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("chapters")
+                .long("chapters")
+                .help("List the chapters of MP4 audiobooks, counting (-c), or verbose (-v)"),
+        )
+        .arg(
+            Arg::new("split-chapters")
+                .long("split-chapters")
+                .help("Split MP4 audiobooks into a track per chapter, remuxing, not re-encoding (needs ffmpeg)"),
+        )
+        .arg(
+            Arg::new("chapter-titles")
+                .long("chapter-titles")
+                .value_name("TEMPLATE")
+                .help("Rewrite the chapter titles of MP4 audiobooks, {n} being the chapter number, {title} the original title (needs ffmpeg)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("log-file")
+                .long("log-file")
+                .value_name("FILE")
+                .help("Append the log of the run to FILE")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("log-level")
+                .long("log-level")
+                .value_name("LEVEL")
+                .possible_values(["error", "warn", "info", "debug", "trace"])
+                .default_value("info")
+                .help("Log up to LEVEL; debug covers sorting, tagging, and skipped files (to stderr, unless --log-file)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("progress")
+                .long("progress")
+                .value_name("MODE")
+                .possible_values(["auto", "terminal", "plain", "json", "silent"])
+                .default_value("auto")
+                .help("Report progress on a terminal (animated), as plain text lines, as JSON lines (one event per line), or not at all; auto picks terminal, or plain, depending on stdout")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("copy-mode")
+                .long("copy-mode")
+                .value_name("MODE")
+                .possible_values(["tmp", "direct", "stream"])
+                .default_value("tmp")
+                .help("Tag via a temporary copy (tmp, default), tag the destination copy in place (direct), or write the new tag and the audio straight to destination (stream; MP3 only, direct otherwise); along with --jobs, tmp works as direct")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("transcode")
+                .long("transcode")
+                .value_name("RULE")
+                .multiple_occurrences(true)
+                .help("Transcode the tracks by the RULE, FROM[,FROM...]:TO[:COMMAND], e.g. \"flac,ape:mp3\", or \"wma:opus:opusenc --bitrate 48 {src} {dst}\" (ffmpeg by default); the COMMAND words are quoted as in a shell; may be repeated")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("replaygain")
+                .long("replaygain")
+                .help("Measure the loudness of the tracks (decoding them by ffmpeg), and write the track and album gain tags: ReplayGain 2.0, or R128 for Opus")
+        )
+        .arg(
+            Arg::new("jobs")
+                .long("jobs")
+                .value_name("N")
+                .help("Copy and tag up to N tracks in advance, in parallel (0 for all the CPUs); the writes stay sequential")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("staging-size")
+                .long("staging-size")
+                .value_name("SIZE")
+                .requires("jobs")
+                .help("Keep no more than SIZE (512M by default) of tracks prepared in advance")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("volume-size")
+                .long("volume-size")
+                .value_name("SIZE")
+                .help("Split the album into volumes of at most SIZE (e.g. 3900M, 2G); track numbering continues across volumes")
+                .conflicts_with("transcode")
                 .takes_value(true),
         )
+        .arg(
+            Arg::new("volume-roots")
+                .long("volume-roots")
+                .requires("volume-size")
+                .help("Write each volume to the destination root, swapping media in between"),
+        )
+        .arg(
+            Arg::new("volume-pause")
+                .long("volume-pause")
+                .requires("volume-size")
+                .help("Pause before each next volume to swap media"),
+        )
         .arg(
             Arg::new("src")
-                .help("Source file or directory")
+                .help("Source directory, file, or archive (ZIP, 7z, tar); several sources make one album, tracks numbered continuously")
                 .required(true)
+                .multiple_values(true)
                 .index(1),
         )
         .arg(
@@ -261,7 +545,47 @@ fn args_retrieve() -> ArgMatches {
                 .required(true)
                 .index(2),
         )
-        .get_matches()
+        .arg(
+            Arg::new("batch")
+                .long("batch")
+                .conflicts_with_all(&["u", "p"])
+                .help("Copy each subdirectory of <src>, or each source, listed in <src> file (one per line), as an album of its own, and sum the results up")
+        )
+        .arg(
+            Arg::new("batch-number")
+                .long("batch-number")
+                .requires("batch")
+                .help("Number the albums of the batch in turn, in sort order, starting with -b, or 1")
+        )
+        .arg(
+            Arg::new("journal")
+                .long("journal")
+                .value_name("FILE")
+                .help("Write the journal of the run to FILE, instead of the XDG state directory")
+                .takes_value(true),
+        )
+        .subcommand(
+            Command::new("undo")
+                .about("Undo a run: remove exactly the files and directories it created")
+                .arg(
+                    Arg::new("journal")
+                        .help("Journal of the run")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Check an album: track numbering, formats, tags, duplicates, and the like")
+                .arg(
+                    Arg::new("src")
+                        .help("Source file or directory")
+                        .required(true)
+                        .index(1),
+                ),
+        )
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
 }
 
 /// Returns a vector of [dir] subdirectories, if [folders] is true,
@@ -271,7 +595,22 @@ fn fs_entries(dir: &Path, folders: bool) -> Result<Vec<PathBuf>, io::Error> {
     Ok(fs::read_dir(dir)?
         .filter(|r| r.is_ok())
         .map(|r| r.unwrap().path())
-        .filter(|r| if folders { r.is_dir() } else { is_audiofile(r) })
+        .filter(|r| {
+            if folders {
+                r.is_dir()
+            } else if r.is_dir() {
+                false
+            } else if is_track(r) {
+                log::trace!("Accepted: \"{}\"", r.display());
+                true
+            } else {
+                log::debug!(
+                    "Skipped (not an audio file, or no match): \"{}\"",
+                    r.display()
+                );
+                false
+            }
+        })
         .collect())
 }
 
@@ -287,6 +626,19 @@ fn dir_offspring(dir: &Path) -> Result<Vec<PathBuf>, io::Error> {
 /// Returns sorted vectors of directories and audiofiles inside [dir].
 ///
 fn dir_groom(dir: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    fn names(paths: &[PathBuf]) -> String {
+        paths
+            .iter()
+            .map(|p| {
+                format!(
+                    "\"{}\"",
+                    p.file_name().unwrap_or_default().to_string_lossy()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     fn sort_lex(dirs: &mut [PathBuf], files: &mut [PathBuf]) {
         dirs.sort_unstable();
         files.sort_unstable();
@@ -309,7 +661,7 @@ fn dir_groom(dir: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
             if flag("r") { reverse } else { reverse_nop };
     }
 
-    if dir.is_file() && is_audiofile(dir) {
+    if dir.is_file() && is_track(dir) {
         return (vec![], vec![dir.to_path_buf()]);
     }
     let mut dirs = fs_entries(dir, true).unwrap();
@@ -318,6 +670,18 @@ fn dir_groom(dir: &Path) -> (Vec<PathBuf>, Vec<PathBuf>) {
     SORT(&mut dirs, &mut files);
     REVERSE(&mut dirs, &mut files);
 
+    log::debug!(
+        "Sorted \"{}\" {}{}: directories [{}]; files [{}]",
+        dir.display(),
+        if flag("x") {
+            "lexicographically"
+        } else {
+            "naturally"
+        },
+        if flag("r") { ", reversed" } else { "" },
+        names(&dirs),
+        names(&files)
+    );
     (dirs, files)
 }
 
@@ -325,6 +689,9 @@ type WalkItem = (PathBuf, Vec<PathBuf>);
 type WalkIterator = Box<dyn Iterator<Item = WalkItem>>;
 type WalkStepDownFn = dyn Fn(PathBuf) -> WalkIterator;
 type WalkMakeItemFn = dyn Fn(PathBuf) -> WalkItem;
+type Disc = Option<(u64, u64)>; // (Volume number, volumes total), if split into volumes.
+type Gain = (&'static str, String); // Gain tag: (name, value).
+type Gains = HashMap<PathBuf, Vec<Gain>>; // Gain tags of the measured tracks.
 
 /// Walks down the (source) [dir] tree, accumulating [step_down] on each recursion level.
 /// Item is a tuple of
@@ -381,24 +748,40 @@ fn dir_walk(dir: &Path, step_down: Vec<PathBuf>) -> WalkIterator {
     STREAM_FILES(dirs, files, Box::new(walk), Box::new(item))
 }
 
-/// Copies [src] to [dst], makes panic sensible.
+/// Walks down the [srcs] trees, one after another, as dir_walk() does;
+/// backwards, the last source first, if the walk is reversed.
+///
+fn srcs_walk(srcs: &[PathBuf]) -> WalkIterator {
+    let mut srcs = srcs.to_vec();
+
+    if flag("r") {
+        srcs.reverse();
+    }
+    Box::new(srcs.into_iter().flat_map(|src| dir_walk(&src, vec![])))
+}
+
+/// Copies [src] to [dst], straight from its archive, if it stands in for
+/// an archive entry (see Streamed); makes panic sensible.
 ///
 fn file_copy(src: &Path, dst: &Path) {
-    fs::copy(src, dst).unwrap_or_else(|_| {
+    let copied = match streamed(src, |s| (s.archive.clone(), s.entry.clone())) {
+        Some((archive, entry)) => archive::entry_copy(&archive, &entry, dst),
+        None => fs::copy(src, dst).map(|_| ()),
+    };
+    copied.unwrap_or_else(|_| {
         panic!(
             "{}Error while copying \"{}\" to \"{}\".{}",
-            BDELIM_ICON,
+            *BDELIM_ICON,
             &src.to_str().unwrap(),
             &dst.to_str().unwrap(),
-            BDELIM_ICON
+            *BDELIM_ICON
         )
     });
 }
 
-/// Sets tags to [dst] audio file, using [ii] and [src] name in the title tag
-/// composition.
+/// Returns the title tag of the [src] track (number [ii]), composed according to options.
 ///
-fn file_set_tags(ii: u64, src: &Path, dst: &Path) {
+fn track_title(ii: u64, src: &Path) -> String {
     fn title_fi(ii: u64, src: &Path) -> String {
         let stem = &src.file_stem().unwrap().to_str().unwrap();
 
@@ -413,26 +796,6 @@ fn file_set_tags(ii: u64, src: &Path, dst: &Path) {
         format!("{} {}", ii, *TITLE_TAIL)
     }
 
-    fn tag_set_track_number(tag: &mut taglib::Tag, ii: u64) {
-        tag.set_track(ii as u32);
-    }
-    fn tag_nop_track_number(_tag: &mut taglib::Tag, _ii: u64) {}
-
-    fn tag_set_artist_album(tag: &mut taglib::Tag, ii: u64, src: &Path) {
-        tag.set_title(&TITLE_COMPOSE(ii, src));
-        tag.set_artist(&ARTIST);
-        tag.set_album(&ALBUM);
-    }
-    fn tag_set_artist(tag: &mut taglib::Tag, ii: u64, src: &Path) {
-        tag.set_title(&TITLE_COMPOSE(ii, src));
-        tag.set_artist(&ARTIST);
-    }
-    fn tag_set_album(tag: &mut taglib::Tag, ii: u64, src: &Path) {
-        tag.set_title(&TITLE_COMPOSE(ii, src));
-        tag.set_album(&ALBUM);
-    }
-    fn tag_nop_all(_tag: &mut taglib::Tag, _ii: u64, _src: &Path) {}
-
     lazy_static! {
         static ref INITIALS: String = if *IS_ARTIST {
             initials(&ARTIST)
@@ -455,6 +818,89 @@ fn file_set_tags(ii: u64, src: &Path, dst: &Path) {
         } else {
             title_i
         };
+    }
+
+    TITLE_COMPOSE(ii, src)
+}
+
+/// Returns the tags, to be set to the [src] track (number [ii]) copy,
+/// according to options, the [gains] included: (frame ID, tag name, value).
+///
+fn track_tags(
+    ii: u64,
+    disc: Disc,
+    gains: &[Gain],
+    src: &Path,
+) -> Vec<(&'static str, &'static str, String)> {
+    let mut tags = vec![];
+
+    if !flag("d") {
+        tags.push(("TRCK", "TRACKNUMBER", ii.to_string()));
+    }
+    if *IS_ARTIST || *IS_ALBUM {
+        tags.push(("TIT2", "TITLE", track_title(ii, src)));
+    }
+    if *IS_ARTIST {
+        tags.push(("TPE1", "ARTIST", ARTIST.to_string()));
+    }
+    if *IS_ALBUM {
+        tags.push(("TALB", "ALBUM", ALBUM.to_string()));
+    }
+    if let Some((volume, volumes_total)) = disc {
+        tags.push((
+            "TPOS",
+            "DISCNUMBER",
+            format!("{}/{}", volume, volumes_total),
+        ));
+    }
+    tags.extend(
+        gains
+            .iter()
+            .map(|(name, value)| ("TXXX", *name, value.clone())),
+    );
+    tags
+}
+
+/// Logs the [tags] (see track_tags()), written to [dst].
+///
+fn tags_log(dst: &Path, tags: &[(&str, &str, String)]) {
+    if log::log_enabled!(log::Level::Debug) {
+        log::debug!(
+            "Tagged \"{}\": {}",
+            dst.display(),
+            tags.iter()
+                .map(|(_, name, value)| format!("{}={:?}", name, value))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+}
+
+/// Sets tags to [dst] audio file, using [ii] and [src] name in the title tag
+/// composition.
+///
+fn file_set_tags(ii: u64, src: &Path, dst: &Path) {
+    fn tag_set_track_number(tag: &mut taglib::Tag, ii: u64) {
+        tag.set_track(ii as u32);
+    }
+    fn tag_nop_track_number(_tag: &mut taglib::Tag, _ii: u64) {}
+
+    fn tag_set_artist_album(tag: &mut taglib::Tag, ii: u64, src: &Path) {
+        tag.set_title(&track_title(ii, src));
+        tag.set_artist(&ARTIST);
+        tag.set_album(&ALBUM);
+    }
+    fn tag_set_artist(tag: &mut taglib::Tag, ii: u64, src: &Path) {
+        tag.set_title(&track_title(ii, src));
+        tag.set_artist(&ARTIST);
+    }
+    fn tag_set_album(tag: &mut taglib::Tag, ii: u64, src: &Path) {
+        tag.set_title(&track_title(ii, src));
+        tag.set_album(&ALBUM);
+    }
+    fn tag_nop_all(_tag: &mut taglib::Tag, _ii: u64, _src: &Path) {}
+
+    lazy_static! {
         static ref TAG_SET_TRACK_NUMBER: fn(&mut taglib::Tag, u64) = if flag("d") {
             tag_nop_track_number
         } else {
@@ -474,14 +920,14 @@ fn file_set_tags(ii: u64, src: &Path, dst: &Path) {
     let tag_file = taglib::File::new(dst).unwrap_or_else(|_| {
         panic!(
             "{}Error while opening \"{}\" for tagging.{}",
-            BDELIM_ICON,
+            *BDELIM_ICON,
             &dst.to_str().unwrap(),
-            BDELIM_ICON
+            *BDELIM_ICON
         )
     });
     let mut tag = tag_file
         .tag()
-        .unwrap_or_else(|_| panic!("{}No tagging data.{}", BDELIM_ICON, BDELIM_ICON));
+        .unwrap_or_else(|_| panic!("{}No tagging data.{}", *BDELIM_ICON, *BDELIM_ICON));
 
     TAG_SET_TRACK_NUMBER(&mut tag, ii);
     TAG_SET_THE_REST(&mut tag, ii, src);
@@ -489,63 +935,221 @@ fn file_set_tags(ii: u64, src: &Path, dst: &Path) {
     tag_file.save();
 }
 
-#[allow(dead_code)]
+/// Sets the disc number tag of [dst] audio file to [disc]
+/// (volume number, volumes total), if any.
+///
+fn file_set_disc(disc: Disc, dst: &Path) {
+    if let Some((volume, volumes_total)) = disc {
+        if !tagprops::properties_set(
+            dst,
+            &[("DISCNUMBER", format!("{}/{}", volume, volumes_total))],
+        ) {
+            panic!(
+                "{}Error while setting disc number of \"{}\".{}",
+                *BDELIM_ICON,
+                &dst.to_str().unwrap(),
+                *BDELIM_ICON
+            )
+        }
+    }
+}
+
+/// Sets the [gains] tags to [dst] audio file, if any.
+///
+fn file_set_gains(gains: &[Gain], dst: &Path) {
+    if !gains.is_empty() && !tagprops::properties_set(dst, gains) {
+        panic!(
+            "{}Error while setting gain tags of \"{}\".{}",
+            *BDELIM_ICON,
+            &dst.to_str().unwrap(),
+            *BDELIM_ICON
+        )
+    }
+}
+
 /// Copies [src] to [dst], sets tags to [dst].
 ///
-fn file_copy_and_set_tags(ii: u64, src: &Path, dst: &Path) {
+fn file_copy_and_set_tags(ii: u64, disc: Disc, gains: &[Gain], src: &Path, dst: &Path) {
     file_copy(src, dst);
     file_set_tags(ii, src, dst);
+    file_set_disc(disc, dst);
+    file_set_gains(gains, dst);
+}
+
+/// Transcodes [src] to [dst] by the [rule], sets tags to [dst].
+///
+fn file_transcode_and_set_tags(
+    rule: &transcode::Rule,
+    ii: u64,
+    disc: Disc,
+    gains: &[Gain],
+    src: &Path,
+    dst: &Path,
+) {
+    transcode::run(rule, src, dst).unwrap_or_else(|e| {
+        panic!(
+            "{}Error while transcoding \"{}\": {}{}",
+            *BDELIM_ICON,
+            &src.to_str().unwrap(),
+            e,
+            *BDELIM_ICON
+        )
+    });
+    file_set_tags(ii, src, dst);
+    file_set_disc(disc, dst);
+    file_set_gains(gains, dst);
 }
 
-#[allow(dead_code)]
 /// Copies [src] to [dst], sets tags using a temporary file.
 ///
-fn file_copy_and_set_tags_via_tmp(ii: u64, src: &Path, dst: &Path) {
+fn file_copy_and_set_tags_via_tmp(ii: u64, disc: Disc, gains: &[Gain], src: &Path, dst: &Path) {
     let tmp_dir = TempDir::new().unwrap(); // Keep it!
     let tmp = tmp_dir.path().join(format!(
         "tmpaudio.{}",
-        &src.extension().unwrap().to_str().unwrap()
+        &dst.extension().unwrap().to_str().unwrap()
     ));
 
     file_copy(src, &tmp);
     file_set_tags(ii, src, &tmp);
+    file_set_disc(disc, &tmp);
+    file_set_gains(gains, &tmp);
     file_copy(&tmp, dst);
 
     fs::remove_file(&tmp).unwrap_or_else(|_| {
         panic!(
             "{}Error while deleting \"{}\" file.{}",
-            BDELIM_ICON,
+            *BDELIM_ICON,
             &tmp.to_str().unwrap(),
-            BDELIM_ICON
+            *BDELIM_ICON
         )
     });
 }
 
-/// Checks the source validity, and its compatibility with the destination.
+/// Copies [src] MP3 to [dst] in one pass: writes the new ID3v2 tag, then
+/// the rest straight from [src], the APEv2 tag at the end, if any, kept;
+/// the ID3v1 tag, if any, is dropped, as its track number, and title go stale.
+/// Returns false, doing nothing, if the [src] tag is beyond simple rewriting,
+/// or [src] cannot be read.
+///
+fn file_copy_and_set_tags_streamed(
+    ii: u64,
+    disc: Disc,
+    gains: &[Gain],
+    src: &Path,
+    dst: &Path,
+) -> bool {
+    let head = || -> io::Result<_> {
+        let (start, _) = id3::payload_range(src)?;
+        let mut file = fs::File::open(src)?;
+        let len = file.metadata()?.len();
+        let mut tag = vec![0; start as usize];
+
+        file.read_exact(&mut tag)?;
+        let rest = (len - id3::v1_size(&mut file, len)?).saturating_sub(start);
+        file.seek(SeekFrom::Start(start))?;
+        Ok((file, tag, rest))
+    };
+    let Ok((file, tag, rest)) = head() else {
+        return false;
+    };
+    let start = tag.len();
+
+    let (major, mut frames) = if start > 0 {
+        match id3::frames_parse(&tag) {
+            Some(parsed) => parsed,
+            None => return false,
+        }
+    } else {
+        (4, vec![])
+    };
+    let tags = track_tags(ii, disc, gains, src);
+
+    frames.retain(|f| {
+        !tags.iter().any(|(id, name, _)| match *id {
+            "TXXX" => f.user_text_description().as_deref() == Some(name),
+            _ => f.id == id.as_bytes(),
+        })
+    });
+    frames.extend(tags.iter().map(|(id, name, t)| match *id {
+        "TXXX" => id3::user_text_frame(major, name, t),
+        _ => id3::text_frame(major, id, t),
+    }));
+
+    let mut out = io::BufWriter::new(fs::File::create(dst).unwrap_or_else(|_| {
+        panic!(
+            "{}Error while creating \"{}\".{}",
+            *BDELIM_ICON,
+            &dst.to_str().unwrap(),
+            *BDELIM_ICON
+        )
+    }));
+    out.write_all(&id3::tag_build(major, &frames))
+        .and_then(|_| io::copy(&mut file.take(rest), &mut out))
+        .and_then(|_| out.flush())
+        .unwrap_or_else(|_| {
+            panic!(
+                "{}Error while copying \"{}\" to \"{}\".{}",
+                *BDELIM_ICON,
+                &src.to_str().unwrap(),
+                &dst.to_str().unwrap(),
+                *BDELIM_ICON
+            )
+        });
+    true
+}
+
+/// Copies [src] to [dst] by file_copy_and_set_tags_streamed(), if it is an MP3;
+/// by file_copy_and_set_tags() otherwise, or if streaming fails.
 ///
-fn src_check() -> (Vec<String>, PathBuf) {
-    let src = pval("src");
+fn file_copy_and_set_tags_stream_mp3(ii: u64, disc: Disc, gains: &[Gain], src: &Path, dst: &Path) {
+    if !(has_ext_of(dst.to_str().unwrap(), "mp3")
+        && sniff::sniff(src).is_ok_and(|c| c == sniff::Container::Mpeg)
+        && file_copy_and_set_tags_streamed(ii, disc, gains, src, dst))
+    {
+        file_copy_and_set_tags(ii, disc, gains, src, dst);
+    }
+}
+
+/// Returns the copy-and-tag function of --copy-mode; [staging] tracks
+/// (see tracks_stage_parallel()) are temporary copies already, so tmp
+/// tags them in place, too.
+///
+fn copy_and_set_tags_of_mode(staging: bool) -> fn(u64, Disc, &[Gain], &Path, &Path) {
+    match sval("copy-mode") {
+        "stream" => file_copy_and_set_tags_stream_mp3,
+        "tmp" if !staging => file_copy_and_set_tags_via_tmp,
+        _ => file_copy_and_set_tags,
+    }
+}
+
+/// Checks the sources validity, and their compatibility with the destination.
+///
+fn src_check(progress: &mut dyn Progress) -> (Vec<String>, Vec<PathBuf>) {
+    let srcs = pvals("src");
     let mut log = Vec::<String>::new();
 
-    if !flag("c") && src.is_dir() && DST_DIR.starts_with(&src) {
+    for src in srcs
+        .iter()
+        .filter(|src| !flag("c") && src.is_dir() && DST_DIR.starts_with(src))
+    {
         let dst_msg = format!(
             " {} Target directory \"{}\"",
-            WARNING_ICON,
+            *WARNING_ICON,
             DST_DIR.display()
         );
-        let src_msg = format!(" {} is inside source \"{}\"", WARNING_ICON, src.display());
+        let src_msg = format!(" {} is inside source \"{}\"", *WARNING_ICON, src.display());
         if flag("y") {
             log.push(dst_msg);
             log.push(src_msg);
-            log.push(format!(" {} It won't run.", WARNING_ICON));
+            log.push(format!(" {} It won't run.", *WARNING_ICON));
         } else {
-            println!("{}", dst_msg);
-            println!("{}", src_msg);
-            println!(" {} No go.", WARNING_ICON);
+            progress.error(&dst_msg);
+            progress.error(&src_msg);
+            progress.error(&format!(" {} No go.", *WARNING_ICON));
             exit(1);
         }
     }
-    (log, src.to_path_buf())
+    (log, srcs)
 }
 
 /// Returns Artist, nicely shaped to be a part of a directory/file name.
@@ -578,15 +1182,13 @@ fn dst_calculate() -> PathBuf {
             format!("{}{}", artist_part(false), *UNIFIED)
         } else {
             let src = pval("src");
-            if src.is_file() {
-                src.file_stem()
+            if let Some(stem) = archive::stem(&src).filter(|_| src.is_file()) {
+                stem
+            } else if src.is_file() {
+                src.file_stem().unwrap().to_str().unwrap().to_string()
             } else {
-                src.file_name()
+                src.file_name().unwrap().to_str().unwrap().to_string()
             }
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .to_string()
         }
     );
     if flag("p") {
@@ -596,44 +1198,163 @@ fn dst_calculate() -> PathBuf {
     }
 }
 
-/// Creates destination directory according to options, if
-/// necessary and possible.
+/// Disposes of the existing destination directory [dst] according to --overwrite-mode:
+/// removes it (default, or after confirmation), renames it to a timestamped
+/// backup, or moves it to the trash.
 ///
-fn dst_create() -> PathBuf {
-    if !flag("p") && !flag("y") {
-        if DST_DIR.exists() {
-            if flag("w") {
-                fs::remove_dir_all(DST_DIR.as_path()).unwrap_or_else(|_| {
-                    panic!(
-                        "{}Failed to remove destination directory \"{}\".{}",
-                        BDELIM_ICON,
-                        DST_DIR.display(),
-                        BDELIM_ICON
-                    )
-                });
-            } else {
-                println!(
-                    " {} Destination directory \"{}\" already exists.",
-                    WARNING_ICON,
-                    DST_DIR.display()
-                );
+fn dst_remove(dst: &Path, progress: &mut dyn Progress) {
+    fn remove(dst: &Path) {
+        fs::remove_dir_all(dst).unwrap_or_else(|_| {
+            panic!(
+                "{}Failed to remove destination directory \"{}\".{}",
+                *BDELIM_ICON,
+                dst.display(),
+                *BDELIM_ICON
+            )
+        });
+        journal::record(journal::Entry::Removed(dst));
+    }
+    fn moved(
+        dst: &Path,
+        to: io::Result<PathBuf>,
+        entry: for<'a> fn(&'a Path, &'a Path) -> journal::Entry<'a>,
+        progress: &mut dyn Progress,
+    ) {
+        match to {
+            Ok(to) => {
+                journal::record(entry(dst, &to));
+                progress.warning(&format!(
+                    " {} \"{}\" moved to \"{}\"",
+                    *BDELIM_ICON,
+                    dst.display(),
+                    to.display()
+                ))
+            }
+            Err(e) => panic!(
+                "{}Failed to move destination directory \"{}\" away: {}.{}",
+                *BDELIM_ICON,
+                dst.display(),
+                e,
+                *BDELIM_ICON
+            ),
+        }
+    }
+
+    match if flag("overwrite-mode") {
+        sval("overwrite-mode")
+    } else {
+        "remove"
+    } {
+        "backup" => {
+            let mut backup = dst.as_os_str().to_os_string();
+            backup.push(format!(".bak-{}", Local::now().format("%Y%m%d-%H%M%S")));
+            moved(
+                dst,
+                fs::rename(dst, &backup).map(|_| PathBuf::from(backup)),
+                |from, to| journal::Entry::Moved(from, to),
+                progress,
+            );
+        }
+        "trash" => moved(
+            dst,
+            trash::trash_move(dst),
+            |from, to| journal::Entry::Trashed(from, to),
+            progress,
+        ),
+        "confirm" => {
+            let (files, bytes) = dir_census(dst);
+            if !prompt_yes(
+                &format!(
+                    "Remove \"{}\", {} file(s), {}?",
+                    dst.display(),
+                    files,
+                    human_fine(bytes)
+                ),
+                false,
+            ) {
+                exit(1);
+            }
+            remove(dst);
+        }
+        _ => remove(dst),
+    }
+}
+
+/// Returns the count and the total size of all the files in [dir] and its subdirectories.
+///
+fn dir_census(dir: &Path) -> (u64, u64) {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .map(|e| {
+                    let p = e.path();
+                    if p.is_dir() {
+                        dir_census(&p)
+                    } else {
+                        (1, p.metadata().map_or(0, |m| m.len()))
+                    }
+                })
+                .fold((0, 0), |(f, b), (ff, bb)| (f + ff, b + bb))
+        })
+        .unwrap_or((0, 0))
+}
+
+/// Creates destination directory according to options, if
+/// necessary and possible.
+///
+fn dst_create(progress: &mut dyn Progress) -> PathBuf {
+    if !flag("p") && !flag("y") {
+        if DST_DIR.exists() {
+            if flag("sync") {
+                return DST_DIR.to_path_buf();
+            } else if flag("w") || flag("overwrite-mode") {
+                dst_remove(&DST_DIR, progress);
+            } else {
+                progress.error(&format!(
+                    " {} Destination directory \"{}\" already exists.",
+                    *WARNING_ICON,
+                    DST_DIR.display()
+                ));
                 exit(1);
             }
         }
         fs::create_dir(DST_DIR.as_path()).unwrap_or_else(|_| {
             panic!(
                 "{}Destination directory \"{}\" already exists!{}",
-                BDELIM_ICON,
+                *BDELIM_ICON,
                 DST_DIR.display(),
-                BDELIM_ICON
+                *BDELIM_ICON
             )
         });
+        journal::record(journal::Entry::Dir(&DST_DIR));
     }
     DST_DIR.to_path_buf()
 }
 
+/// Creates [dir] along with its missing ancestors, journaling each one created.
+///
+fn dir_create(dir: &Path) {
+    if dir.is_dir() {
+        return;
+    }
+    if let Some(parent) = dir.parent() {
+        dir_create(parent);
+    }
+    fs::create_dir(dir).unwrap_or_else(|_| {
+        panic!(
+            "{}Error while creating \"{}\" directory.{}",
+            *BDELIM_ICON,
+            dir.display(),
+            *BDELIM_ICON
+        )
+    });
+    journal::record(journal::Entry::Dir(dir));
+}
+
 /// Extracts file name from the [src] track (number [ii])
-/// and makes it pretty, if necessary.
+/// and makes it pretty, if necessary. Fixes the extension
+/// to fit the content, if asked to.
 ///
 fn track_decorate(ii: u64, src: &Path, step: &[PathBuf], width: usize) -> PathBuf {
     fn prefix_subdir_make(ii: u64, step: &[PathBuf], width: usize) -> String {
@@ -671,6 +1392,22 @@ fn track_decorate(ii: u64, src: &Path, step: &[PathBuf], width: usize) -> PathBu
         PathBuf::from(src.file_name().unwrap())
     }
 
+    fn ext_fix(src: &Path, name: PathBuf) -> PathBuf {
+        match sniff::ext_mismatch(src) {
+            Some((_, ext)) => name.with_extension(ext),
+            None => name,
+        }
+    }
+    fn ext_nop(_src: &Path, name: PathBuf) -> PathBuf {
+        name
+    }
+    fn ext_transcode(src: &Path, name: PathBuf) -> PathBuf {
+        match transcode_rule(src) {
+            Some(rule) if is_audiofile(src) => name.with_extension(&rule.to),
+            _ => name,
+        }
+    }
+
     lazy_static! {
         static ref PREFIX_MAKE: fn(u64, &[PathBuf], usize) -> String = if flag("i") && !flag("t") {
             prefix_subdir_make
@@ -685,355 +1422,2379 @@ fn track_decorate(ii: u64, src: &Path, step: &[PathBuf], width: usize) -> PathBu
             } else {
                 decorate
             };
+        static ref EXT_FIX: fn(&Path, PathBuf) -> PathBuf =
+            if flag("fix-ext") { ext_fix } else { ext_nop };
+        static ref EXT_TRANSCODE: fn(&Path, PathBuf) -> PathBuf = if flag("transcode") {
+            ext_transcode
+        } else {
+            ext_nop
+        };
+    }
+
+    EXT_TRANSCODE(src, EXT_FIX(src, DECORATE(ii, src, step, width)))
+}
+
+/// Returns the name of the chunk subdirectory for the track number [ii],
+/// [per_dir] tracks per chunk, e.g. "001-099", "100-198", ... The last chunk
+/// ends with [tracks_total]. If all the tracks fit into one directory,
+/// there is no chunk to speak of.
+///
+fn chunk_name(ii: u64, per_dir: u64, tracks_total: u64, width: usize) -> String {
+    if tracks_total <= per_dir {
+        return "".to_string();
+    }
+    let first = (ii - 1) / per_dir * per_dir + 1;
+    let last = cmp::min(first + per_dir - 1, tracks_total);
+
+    format!("{:02$}-{:02$}", first, last, width)
+}
+
+/// Technical properties of an audio file, as reported by taglib.
+///
+#[derive(Clone, Copy, Default)]
+struct AudioProps {
+    seconds: u32,
+    bitrate: u32,     // kbps.
+    sample_rate: u32, // Hz.
+    channels: u32,
+}
+
+/// Returns the technical properties of the [path] audio file, if readable.
+///
+fn audio_props(path: &Path) -> Option<AudioProps> {
+    if let Some(props) = streamed(path, |s| s.props) {
+        return props;
+    }
+    let file = taglib::File::new(path).ok()?;
+    let props = file.audioproperties().ok()?;
+
+    Some(AudioProps {
+        seconds: props.length(),
+        bitrate: props.bitrate(),
+        sample_rate: props.samplerate(),
+        channels: props.channels(),
+    })
+}
+
+/// Measures the loudness of the [tracks] on [jobs] threads, and returns their gain
+/// tags; the album gain is the one of all the [tracks].
+///
+fn gains_compute(tracks: &[Track], jobs: usize, log: &mut Vec<String>) -> Gains {
+    let next = AtomicUsize::new(0);
+    let measured: Mutex<Vec<Option<io::Result<loudness::Loudness>>>> =
+        Mutex::new(tracks.iter().map(|_| None).collect());
+
+    log::info!("Measuring loudness of {} track(s)", tracks.len());
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(tracks.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= tracks.len() {
+                    return;
+                }
+                let src = &tracks[i].src;
+                let channels = audio_props(src).map_or(2, |p| p.channels.max(1) as usize);
+                let result = loudness::analyze(src, channels);
+
+                measured.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+
+    let measured: Vec<_> = measured
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .flatten()
+        .collect();
+    if measured.iter().any(|m| {
+        m.as_ref()
+            .is_err_and(|e| e.kind() == io::ErrorKind::NotFound)
+    }) {
+        log::warn!("No ffmpeg found; no gain tags written");
+        log.push(format!(
+            " {} No ffmpeg found to measure loudness; no gain tags written.",
+            *WARNING_ICON
+        ));
+        return Gains::new();
+    }
+    for (track, result) in tracks.iter().zip(&measured) {
+        if let Err(e) = result {
+            log::warn!("Failed to measure \"{}\": {}", track.src.display(), e);
+            log.push(format!(
+                " {} Failed to measure loudness of \"{}\": {}",
+                *WARNING_ICON,
+                track.src.display(),
+                e
+            ));
+        }
+    }
+
+    let album: Vec<&loudness::Loudness> = measured.iter().flatten().collect();
+    let mut gains = Gains::new();
+
+    for (track, result) in tracks.iter().zip(&measured) {
+        if let Ok(measured) = result {
+            let opus = match transcode_rule(&track.src) {
+                Some(rule) => rule.to == "opus",
+                None => sniff::sniff(&track.src)
+                    .is_ok_and(|c| c == sniff::Container::Ogg(sniff::OggCodec::Opus)),
+            };
+            let tags = loudness::gain_tags(opus, measured, &album);
+
+            log::debug!("Gain: \"{}\": {:?}", track.src.display(), tags);
+            gains.insert(track.src.clone(), tags);
+        }
     }
+    gains
+}
+
+/// Returns the gain tags of the [src] track, if measured (see gains_compute()).
+///
+fn gains_of<'a>(gains: &'a Gains, src: &Path) -> &'a [Gain] {
+    gains.get(src).map_or(&[], |g| g)
+}
 
-    DECORATE(ii, src, step, width)
+/// Returns the total duration of the tracks to be copied, seconds
+/// (see the properties in the [census]), the [skipped] ones left out.
+///
+fn duration_total(census: &Census, skipped: &HashSet<PathBuf>) -> u64 {
+    census
+        .props
+        .iter()
+        .filter(|(p, _)| !skipped.contains(*p))
+        .map(|(_, a)| a.seconds as u64)
+        .sum()
+}
+
+/// A valid track, its destination calculated.
+///
+struct Track {
+    ii: u64,
+    disc: Disc,
+    src: PathBuf,
+    dst: PathBuf, // Complete with the file name.
+    props: Option<AudioProps>,
+    origin: Option<PathBuf>, // The file it was cut, or merged from, or links to, if mirrored.
+    chapters: Vec<mp4::Chapter>, // Found in the source, if any.
 }
 
 /// Calculates destination, complete with the file name,
-/// for the [src_file] track to be copied to and
-/// makes the copy of the valid track (number [ii]).
+/// for the [src_file] track (number [ii]) to be copied to.
 ///
-fn track_copy(
+fn track_dst(
     ii: u64,
+    disc: Disc,
     src_file: &Path,
     step: &[PathBuf], // All the subdirectories to be created below [dst].
     dst: &Path,       // Basic destination directory.
     width: usize,
     tracks_total: u64,
+) -> PathBuf {
+    fn step_flat_collect(
+        _ii: u64,
+        _step: &[PathBuf],
+        _tracks_total: u64,
+        _width: usize,
+    ) -> PathBuf {
+        PathBuf::new()
+    }
+    fn step_chunk_collect(ii: u64, _step: &[PathBuf], tracks_total: u64, width: usize) -> PathBuf {
+        PathBuf::from(chunk_name(ii, *MAX_PER_DIR, tracks_total, width))
+    }
+    fn step_collect(_ii: u64, step: &[PathBuf], _tracks_total: u64, _width: usize) -> PathBuf {
+        step.iter().collect()
+    }
+
+    lazy_static! {
+        static ref MAX_PER_DIR: u64 = if flag("max-per-dir") {
+            ival("max-per-dir").max(1) as u64
+        } else {
+            0
+        };
+        static ref STEP_COLLECT: fn(u64, &[PathBuf], u64, usize) -> PathBuf = if flag("t") {
+            step_collect
+        } else if flag("max-per-dir") {
+            step_chunk_collect
+        } else {
+            step_flat_collect
+        };
+    }
+
+    volume_dst(dst, disc)
+        .join(STEP_COLLECT(ii, step, tracks_total, width))
+        .join(track_decorate(ii, src_file, step, width))
+}
+
+/// Makes the copy of the valid [track], or just writes it to its destination,
+/// if [staged] in advance.
+///
+fn track_copy(
+    track: &Track,
+    staged: Option<&Path>,
+    gains: &Gains,
+    tracks_total: u64,
+    progress: &mut dyn Progress,
     log: &mut Vec<String>,
 ) {
-    fn step_flat_collect(_step: &[PathBuf]) -> PathBuf {
-        PathBuf::new()
+    fn step_nop_create_dir(_dst_dir: &Path) {}
+
+    fn step_create_dir(dst_dir: &Path) {
+        dir_create(dst_dir);
+    }
+
+    #[allow(clippy::ptr_arg)]
+    fn file_nop_copytags(
+        _ii: u64,
+        _disc: Disc,
+        _gains: &[Gain],
+        _src: &Path,
+        _staged: Option<&Path>,
+        _dst: &Path,
+        _log: &mut Vec<String>,
+    ) -> u64 {
+        0
+    }
+
+    fn file_copytags(
+        ii: u64,
+        disc: Disc,
+        gains: &[Gain],
+        src: &Path,
+        staged: Option<&Path>, // Copied and tagged in advance, if any.
+        dst: &Path,
+        log: &mut Vec<String>,
+    ) -> u64 {
+        let mut dst_bytes: u64 = 0;
+
+        if dst.is_file() {
+            log::warn!("Skipped (already there): \"{}\"", dst.display());
+            log.push(format!(
+                " {} File \"{}\" already copied. Review your options.",
+                *WARNING_ICON,
+                &dst.file_name().unwrap().to_str().unwrap()
+            ));
+        } else {
+            let taggable = is_taggable(src, dst);
+
+            match (staged, transcode_rule(src)) {
+                (Some(staged), _) => file_copy(staged, dst),
+                (None, Some(rule)) if taggable && is_audiofile(src) => {
+                    file_transcode_and_set_tags(rule, ii, disc, gains, src, dst)
+                }
+                (None, _) if taggable => COPY_AND_SET_TAGS(ii, disc, gains, src, dst),
+                (None, _) => file_copy(src, dst),
+            }
+            journal::record(journal::Entry::File(dst));
+            if taggable {
+                tags_log(dst, &track_tags(ii, disc, gains, src));
+            } else {
+                log::info!("Copied untagged: \"{}\"", dst.display());
+            }
+            dst_bytes = dst.metadata().unwrap().len();
+        }
+        dst_bytes
+    }
+
+    type FileCopyTagsFn =
+        fn(u64, Disc, &[Gain], &Path, Option<&Path>, &Path, &mut Vec<String>) -> u64;
+
+    lazy_static! {
+        static ref STEP_CREATE_DIR: fn(&Path) = if (flag("t") || flag("max-per-dir")) && !flag("y")
+        {
+            step_create_dir
+        } else {
+            step_nop_create_dir
+        };
+        static ref FILE_COPYTAGS: FileCopyTagsFn = if flag("y") {
+            file_nop_copytags
+        } else {
+            file_copytags
+        };
+        static ref COPY_AND_SET_TAGS: fn(u64, Disc, &[Gain], &Path, &Path) =
+            copy_and_set_tags_of_mode(false);
+    }
+
+    STEP_CREATE_DIR(track.dst.parent().unwrap());
+
+    progress.track_started(track.ii, &track.src, &track.dst);
+    log::info!(
+        "Track {}: \"{}\" -> \"{}\"",
+        track.ii,
+        track.src.display(),
+        track.dst.display()
+    );
+
+    let dst_bytes = FILE_COPYTAGS(
+        track.ii,
+        track.disc,
+        gains_of(gains, &track.src),
+        &track.src,
+        staged,
+        &track.dst,
+        log,
+    );
+
+    if !flag("y") && dst_bytes > 0 && !track.chapters.is_empty() {
+        chapters_keep(&track.chapters, &track.dst, log);
+    }
+
+    progress.track_finished(
+        track.ii,
+        tracks_total,
+        &track.dst,
+        track.src.metadata().unwrap().len(),
+        dst_bytes,
+        track.props,
+    );
+    if flag("chapters") && flag("v") {
+        let path = if flag("y") { &track.src } else { &track.dst };
+        chapters_report(path, progress);
+    }
+}
+
+/// Returns the chapters of the [path] file, if it is an MP4 one with chapters.
+///
+fn chapters_of(path: &Path) -> Vec<mp4::Chapter> {
+    if let Some(chapters) = streamed(path, |s| s.chapters.clone()) {
+        return chapters;
+    }
+    if sniff::sniff(path).is_ok_and(|c| c == sniff::Container::Mp4) {
+        mp4::chapters(path).unwrap_or_default()
+    } else {
+        vec![]
+    }
+}
+
+/// Reports the chapters of the [path] file, if any.
+///
+fn chapters_report(path: &Path, progress: &mut dyn Progress) {
+    let chapters = chapters_of(path);
+
+    if !chapters.is_empty() {
+        progress.chapters(path, &chapters);
+    }
+}
+
+/// Rewrites the chapter titles of the [dst] copy of an MP4 file with [chapters],
+/// if asked to, and makes sure the chapters survived copying and tagging.
+///
+fn chapters_keep(chapters: &[mp4::Chapter], dst: &Path, log: &mut Vec<String>) {
+    if flag("chapter-titles") {
+        let titled: Vec<mp4::Chapter> = chapters
+            .iter()
+            .enumerate()
+            .map(|(i, c)| mp4::Chapter {
+                title: mp4::title_render(sval("chapter-titles"), i + 1, &c.title),
+                ..c.clone()
+            })
+            .collect();
+        if let Err(e) = mp4::retitle(dst, &titled) {
+            log::warn!("Failed to retitle chapters of \"{}\": {}", dst.display(), e);
+            log.push(format!(
+                " {} Failed to retitle chapters of \"{}\": {}",
+                *WARNING_ICON,
+                dst.display(),
+                e
+            ));
+        }
+    }
+    let kept = chapters_of(dst).len();
+    if kept < chapters.len() {
+        log::warn!(
+            "Chapters lost: \"{}\": {} of {}",
+            dst.display(),
+            kept,
+            chapters.len()
+        );
+        log.push(format!(
+            " {} Chapters lost: \"{}\": {} of {} kept",
+            *WARNING_ICON,
+            dst.display(),
+            kept,
+            chapters.len()
+        ));
+    }
+}
+
+/// Returns the title of the [track] for a playlist: the one written to
+/// the title tag, if any; otherwise the one already there, or the file name.
+///
+fn playlist_title(track: &Track) -> String {
+    if *IS_ARTIST || *IS_ALBUM {
+        return track_title(track.ii, &track.src);
+    }
+    let file = taglib::File::new(&track.src).ok();
+
+    file.as_ref()
+        .and_then(|f| f.tag().ok())
+        .and_then(|t| t.title())
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or_else(|| track.dst.file_stem().unwrap().to_string_lossy().to_string())
+}
+
+/// Writes the playlists of the [tracks], in the order of their numbers,
+/// into the [dst] root, one per format asked for.
+///
+fn playlists_write(dst: &Path, tracks: &[Track], log: &mut Vec<String>) {
+    let mut ordered: Vec<&Track> = tracks.iter().collect();
+    ordered.sort_by_key(|t| t.ii);
+
+    let entries: Vec<playlist::Entry> = ordered
+        .iter()
+        .map(|t| playlist::Entry {
+            path: t.dst.strip_prefix(dst).unwrap_or(&t.dst).to_path_buf(),
+            seconds: t.props.map(|a| a.seconds),
+            title: playlist_title(t),
+        })
+        .collect();
+    let stem = dst
+        .file_name()
+        .map_or("playlist".into(), |n| n.to_string_lossy());
+
+    for format in ARGS.values_of("playlist").unwrap() {
+        let path = dst.join(format!("{}.{}", stem, format));
+        let is_new = !path.exists();
+
+        match fs::write(&path, playlist::render(format, &entries)) {
+            Ok(_) => {
+                log::info!("Playlist: \"{}\"", path.display());
+                if is_new {
+                    journal::record(journal::Entry::File(&path));
+                }
+            }
+            Err(e) => {
+                log::warn!("Failed to write \"{}\": {}", path.display(), e);
+                log.push(format!(
+                    " {} Failed to write playlist \"{}\": {}",
+                    *WARNING_ICON,
+                    path.display(),
+                    e
+                ));
+            }
+        }
+    }
+}
+
+/// Copies all the valid tracks, counted in the [census], to their destination,
+/// according to the options and GlobalState; the [skipped] duplicates left behind.
+/// The mirrored tracks are known by their [origins] (see src_mirror()).
+///
+#[allow(clippy::too_many_arguments)]
+fn album_copy(
+    now: &Instant,
+    srcs: &[PathBuf],
+    dst: &Path,
+    census: &Census,
+    skipped: &HashSet<PathBuf>,
+    origins: &HashMap<PathBuf, PathBuf>,
+    progress: &mut dyn Progress,
+    log: &mut Vec<String>,
+) {
+    fn tracks_range(range: u64) -> Box<dyn Iterator<Item = u64>> {
+        Box::new(1_u64..=range)
+    }
+    fn tracks_range_rev(range: u64) -> Box<dyn Iterator<Item = u64>> {
+        Box::new((1_u64..=range).rev())
+    }
+
+    lazy_static! {
+        static ref JOBS: usize = match flag("jobs").then(|| ival("jobs")) {
+            Some(n) if n > 0 => n as usize,
+            _ => thread::available_parallelism().map_or(2, |n| n.get()),
+        };
+        static ref STAGING_SIZE: u64 = if flag("staging-size") {
+            human_parse(sval("staging-size")).expect("Staging size must be like 700M, or 3.5G!")
+        } else {
+            512 * 1024 * 1024
+        };
+        static ref VOLUME_SIZE: u64 = if flag("volume-size") {
+            human_parse(sval("volume-size")).expect("Volume size must be like 700M, or 3.5G!")
+        } else {
+            0
+        };
+        static ref RANGE: fn(u64) -> Box<dyn Iterator<Item = u64>> = if flag("r") {
+            tracks_range_rev
+        } else {
+            tracks_range
+        };
+    }
+
+    let (tracks_total, bytes_total) = (census.tracks, census.bytes);
+
+    if tracks_total < 1 {
+        let src = join(srcs.iter().map(|s| format!("\"{}\"", s.display())), ", ");
+
+        log::error!("No audio files found at {}", src);
+        progress.error(&format!(
+            " {} No audio files found at {}",
+            *WARNING_ICON, src
+        ));
+        exit(1);
+    }
+
+    let width = format!("{}", tracks_total).len();
+    let walk: Vec<(u64, WalkItem)> = zip_eq(
+        RANGE(tracks_total),
+        srcs_walk(srcs).filter(|(p, _)| !skipped.contains(p)),
+    )
+    .collect();
+    let volumes = if flag("volume-size") {
+        let mut sizes = vec![0; tracks_total as usize];
+        for (i, (src, _)) in &walk {
+            sizes[*i as usize - 1] = src.metadata().unwrap().len() + TRACK_HEADROOM;
+        }
+        volumes_calculate(&sizes, *VOLUME_SIZE).unwrap_or_else(|ii| {
+            log::error!("Track {} alone exceeds the volume size", ii);
+            progress.error(&format!(
+                " {} Track {} alone exceeds the volume size of {}",
+                *WARNING_ICON,
+                ii,
+                human_fine(*VOLUME_SIZE)
+            ));
+            exit(1);
+        })
+    } else {
+        vec![]
+    };
+    let volumes_total = volumes.last().copied().unwrap_or(0);
+    let tracks: Vec<Track> = walk
+        .into_iter()
+        .map(|(ii, (src, step))| {
+            let disc = if volumes_total > 0 {
+                Some((volumes[ii as usize - 1], volumes_total))
+            } else {
+                None
+            };
+            Track {
+                ii,
+                disc,
+                dst: track_dst(ii, disc, &src, &step, dst, width, tracks_total),
+                props: census.props.get(&src).copied(),
+                origin: origins.get(&src).cloned(),
+                chapters: census.chapters.get(&src).cloned().unwrap_or_default(),
+                src,
+            }
+        })
+        .collect();
+    let copies = if flag("sync") {
+        album_sync(dst, &tracks, progress, log)
+    } else {
+        tracks.iter().collect()
+    };
+    let gains = if flag("replaygain") && !flag("y") && !copies.is_empty() {
+        gains_compute(&tracks, *JOBS, log)
+    } else {
+        Gains::new()
+    };
+    let copy_bytes: u64 = copies
+        .iter()
+        .map(|t| t.src.metadata().map_or(0, |m| m.len()))
+        .sum();
+    let copy_start = Instant::now();
+
+    progress.copy_start(copies.len() as u64, copy_bytes);
+
+    let mut volume_current = 0;
+    let mut commit = |track: &Track, staged: Option<&Path>| {
+        if let Some((volume, _)) = track.disc {
+            if volume != volume_current {
+                volume_enter(dst, track.disc, volume_current != 0, progress);
+                volume_current = volume;
+            }
+        }
+        track_copy(track, staged, &gains, tracks_total, progress, log);
+    };
+
+    if (flag("jobs") || flag("transcode")) && !flag("y") {
+        tracks_stage_parallel(&copies, &gains, *JOBS, *STAGING_SIZE, &mut commit);
+    } else {
+        for track in copies {
+            commit(track, None);
+        }
+    }
+
+    let throughput = if !flag("y") && copy_bytes > 0 {
+        Some((copy_bytes as f64 / copy_start.elapsed().as_secs_f64().max(0.001)) as u64)
+    } else {
+        None
+    };
+
+    if flag("sync") && !flag("y") {
+        let is_new = !dst.join(manifest::MANIFEST_NAME).exists();
+
+        match manifest::write(dst, &sync_signature(), &tracks_records(dst, &tracks)) {
+            Ok(_) if is_new => {
+                journal::record(journal::Entry::File(&dst.join(manifest::MANIFEST_NAME)))
+            }
+            Ok(_) => (),
+            Err(_) => log.push(format!(
+                " {} Failed to write \"{}\"; sync mode will not work.",
+                *WARNING_ICON,
+                manifest::MANIFEST_NAME
+            )),
+        }
+    }
+    if flag("playlist") && !flag("y") && !flag("volume-roots") {
+        playlists_write(dst, &tracks, log);
+    }
+    progress.done(
+        tracks_total,
+        bytes_total,
+        now.elapsed().as_secs_f64(),
+        throughput,
+    );
+}
+
+/// Copies and tags [tracks] (the [gains] included) into a staging directory
+/// on [jobs] worker threads, with no more than [staging_limit] bytes staged
+/// at a time, while [commit] writes them to their destination on the calling
+/// thread, strictly in order.
+///
+fn tracks_stage_parallel(
+    tracks: &[&Track],
+    gains: &Gains,
+    jobs: usize,
+    staging_limit: u64,
+    commit: &mut dyn FnMut(&Track, Option<&Path>),
+) {
+    struct Staging {
+        next: usize, // The next track to be claimed by a worker.
+        bytes: u64,  // Staged, or being staged.
+        abort: bool,
+    }
+
+    let staging_dir = TempDir::new().unwrap();
+    let copy_and_set_tags = copy_and_set_tags_of_mode(true);
+    let sizes: Vec<u64> = tracks
+        .iter()
+        .map(|t| t.src.metadata().unwrap().len())
+        .collect();
+    let staging = (
+        Mutex::new(Staging {
+            next: 0,
+            bytes: 0,
+            abort: false,
+        }),
+        Condvar::new(),
+    );
+    let (tx, rx) = mpsc::channel::<(usize, Option<PathBuf>)>();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let tx = tx.clone();
+            let (staging, sizes, staging_dir) = (&staging, &sizes, staging_dir.path());
+
+            scope.spawn(move || loop {
+                // Tracks are claimed, and staging space is reserved, in the write order,
+                // so the track the writer is waiting for never starves.
+                let i = {
+                    let (lock, cvar) = staging;
+                    let mut state = lock.lock().unwrap();
+                    loop {
+                        if state.abort || state.next >= tracks.len() {
+                            return;
+                        }
+                        if state.bytes == 0 || state.bytes + sizes[state.next] <= staging_limit {
+                            state.bytes += sizes[state.next];
+                            state.next += 1;
+                            break state.next - 1;
+                        }
+                        state = cvar.wait(state).unwrap();
+                    }
+                };
+                let t = tracks[i];
+                let stage = staging_dir.join(format!(
+                    "{}.{}",
+                    t.ii,
+                    t.dst.extension().unwrap().to_str().unwrap()
+                ));
+                let done = panic::catch_unwind(AssertUnwindSafe(|| {
+                    let taggable = is_taggable(&t.src, &t.dst);
+                    let gains = gains_of(gains, &t.src);
+
+                    match transcode_rule(&t.src) {
+                        Some(rule) if taggable && is_audiofile(&t.src) => {
+                            file_transcode_and_set_tags(rule, t.ii, t.disc, gains, &t.src, &stage)
+                        }
+                        _ if taggable => copy_and_set_tags(t.ii, t.disc, gains, &t.src, &stage),
+                        _ => file_copy(&t.src, &stage),
+                    }
+                }));
+                if tx.send((i, done.ok().map(|_| stage))).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(tx);
+
+        let (lock, cvar) = &staging;
+        let mut ready = BTreeMap::new();
+
+        for (i, track) in tracks.iter().enumerate() {
+            let stage = loop {
+                if let Some(stage) = ready.remove(&i) {
+                    break stage;
+                }
+                let (j, stage) = rx.recv().unwrap();
+                ready.insert(j, stage);
+            };
+            let stage = stage.unwrap_or_else(|| {
+                lock.lock().unwrap().abort = true;
+                cvar.notify_all();
+                panic!(
+                    "{}Error while preparing \"{}\".{}",
+                    *BDELIM_ICON,
+                    track.src.display(),
+                    *BDELIM_ICON
+                )
+            });
+            // Workers waiting for staging space would wait forever otherwise.
+            if let Err(e) = panic::catch_unwind(AssertUnwindSafe(|| commit(track, Some(&stage)))) {
+                lock.lock().unwrap().abort = true;
+                cvar.notify_all();
+                panic::resume_unwind(e);
+            }
+            fs::remove_file(&stage).ok();
+            lock.lock().unwrap().bytes -= sizes[i];
+            cvar.notify_all();
+        }
+    });
+}
+
+/// Returns the options affecting the tags; sync mode rewrites everything,
+/// if they change.
+///
+fn sync_signature() -> String {
+    join(
+        ["d", "f", "F"]
+            .iter()
+            .filter(|o| flag(o))
+            .map(|o| format!("-{}", o))
+            .chain(
+                ["a", "m", "u"]
+                    .iter()
+                    .filter(|o| flag(o))
+                    .map(|o| format!("-{} {:?}", o, sval(o))),
+            )
+            .chain(
+                ARGS.values_of("transcode")
+                    .into_iter()
+                    .flatten()
+                    .map(|r| format!("--transcode {:?}", r)),
+            )
+            .chain(flag("replaygain").then(|| "--replaygain".to_string())),
+        " ",
+    )
+}
+
+/// Returns the manifest records of [tracks], [dst] being the destination directory.
+/// The mirrored tracks are recorded by their original paths, not the temporary
+/// ones (see src_mirror(), album_merge()): the pieces, and the merged tracks by
+/// the paths of the files they were cut, or merged from, their names appended.
+///
+fn tracks_records(dst: &Path, tracks: &[Track]) -> Vec<manifest::Record> {
+    tracks
+        .iter()
+        .map(|t| {
+            let rel = t.dst.strip_prefix(dst).unwrap();
+            let disc = t
+                .disc
+                .map_or("".to_string(), |(v, vt)| format!("{}/{}", v, vt));
+
+            match &t.origin {
+                Some(whole) if !t.src.is_symlink() => manifest::Record {
+                    src: whole.join(t.src.file_name().unwrap()),
+                    ..manifest::Record::new(rel, whole, t.ii, disc)
+                },
+                Some(original) => manifest::Record::new(rel, original, t.ii, disc),
+                None => manifest::Record::new(rel, &t.src, t.ii, disc),
+            }
+        })
+        .collect()
+}
+
+/// Brings the existing [dst] album in line with [tracks]: moves the stale files
+/// to the trash (removes them, if there is no trash), renames (and retags)
+/// the shifted ones. Returns the tracks still to be copied.
+/// Offers a full rewrite, if the physical copy order cannot be kept.
+///
+fn album_sync<'a>(
+    dst: &Path,
+    tracks: &'a [Track],
+    progress: &mut dyn Progress,
+    log: &mut Vec<String>,
+) -> Vec<&'a Track> {
+    use manifest::Action;
+
+    let old = manifest::read(dst);
+    let (actions, stale) = match &old {
+        Some((signature, old)) if *signature == sync_signature() => {
+            manifest::plan(old, &tracks_records(dst, tracks))
+        }
+        Some((_, old)) => {
+            log.push(format!(
+                " {} Tag options changed since the last run; all the tracks get copied.",
+                *WARNING_ICON
+            ));
+            manifest::plan(old, &[])
+        }
+        None => (vec![], vec![]),
+    };
+    let mut actions: Vec<Action> = if actions.is_empty() {
+        tracks.iter().map(|_| Action::Copy).collect()
+    } else {
+        actions
+    };
+    let is_unknown = old.is_none() && fs::read_dir(dst).is_ok_and(|mut d| d.next().is_some());
+
+    if (is_unknown || manifest::is_order_broken(&actions)) && !flag("y") {
+        let question = if is_unknown {
+            format!(
+                "\"{}\" was not created by procrustes --sync; rewrite it completely?",
+                dst.display()
+            )
+        } else {
+            "New tracks break the physical copy order; rewrite everything?".to_string()
+        };
+        if !prompt_yes(&question, true) {
+            exit(1);
+        }
+        dst_remove(dst, progress);
+        dir_create(dst);
+        return tracks.iter().collect();
+    }
+
+    if !flag("y") {
+        for rel in &stale {
+            let path = dst.join(rel);
+            match trash::trash_move(&path) {
+                Ok(trashed) => journal::record(journal::Entry::Trashed(&path, &trashed)),
+                Err(_) if fs::remove_file(&path).is_ok() => {
+                    journal::record(journal::Entry::Removed(&path))
+                }
+                Err(_) => (),
+            }
+            dir_prune(path.parent().unwrap(), dst);
+        }
+        // Park the files to be renamed first, for their old and new names may overlap.
+        let mut parked: Vec<(PathBuf, PathBuf, &Track, bool)> = vec![];
+        for (action, track) in zip_eq(actions.iter_mut(), tracks) {
+            if let Action::Rename(old, retag) = action {
+                let mut park = dst.join(&old).into_os_string();
+                park.push(".procrustes-tmp");
+                if fs::rename(dst.join(&old), &park).is_ok() {
+                    parked.push((dst.join(&old), PathBuf::from(park), track, *retag));
+                } else {
+                    *action = Action::Copy; // Gone missing.
+                }
+            }
+        }
+        for (old, park, track, retag) in parked {
+            dir_create(track.dst.parent().unwrap());
+            fs::rename(&park, &track.dst).unwrap_or_else(|_| {
+                panic!(
+                    "{}Error while renaming \"{}\" to \"{}\".{}",
+                    *BDELIM_ICON,
+                    park.display(),
+                    track.dst.display(),
+                    *BDELIM_ICON
+                )
+            });
+            journal::record(journal::Entry::Moved(&old, &track.dst));
+            if retag && is_taggable(&track.src, &track.dst) {
+                file_set_tags(track.ii, &track.src, &track.dst);
+                file_set_disc(track.disc, &track.dst);
+                // The gain tags, if any, stay as measured, when copied.
+                tags_log(
+                    &track.dst,
+                    &track_tags(track.ii, track.disc, &[], &track.src),
+                );
+            }
+            dir_prune(park.parent().unwrap(), dst);
+        }
+    }
+
+    let renamed = actions
+        .iter()
+        .filter(|a| matches!(a, Action::Rename(_, _)))
+        .count();
+    let copied = actions.iter().filter(|a| **a == Action::Copy).count();
+
+    log.push(format!(
+        " {} Sync: {} kept, {} renamed, {} to copy, {} trashed.",
+        *DONE_ICON,
+        tracks.len() - renamed - copied,
+        renamed,
+        copied,
+        stale.len()
+    ));
+    zip_eq(actions, tracks)
+        .filter(|(a, t)| {
+            if *a != Action::Copy {
+                log::debug!("Sync, skipped (up to date): \"{}\"", t.dst.display());
+            }
+            *a == Action::Copy
+        })
+        .map(|(_, t)| t)
+        .collect()
+}
+
+/// Removes [dir] and its ancestors up to (not including) [root], as long as they are empty.
+///
+fn dir_prune(dir: &Path, root: &Path) {
+    let mut dir = dir.to_path_buf();
+
+    while dir.starts_with(root) && dir != root && fs::remove_dir(&dir).is_ok() {
+        dir.pop();
+    }
+}
+
+/// Distributes tracks of [sizes] (in track number order) over volumes
+/// of [limit] bytes at most, filling each volume in turn.
+/// Returns the volume number of each track, or the number of the first track
+/// that does not fit into an empty volume.
+///
+fn volumes_calculate(sizes: &[u64], limit: u64) -> Result<Vec<u64>, u64> {
+    let mut volume = 1;
+    let mut volume_bytes = 0;
+    let mut volumes = Vec::with_capacity(sizes.len());
+
+    for (i, &size) in sizes.iter().enumerate() {
+        if size > limit {
+            return Err(i as u64 + 1);
+        }
+        if volume_bytes + size > limit {
+            volume += 1;
+            volume_bytes = 0;
+        }
+        volume_bytes += size;
+        volumes.push(volume);
+    }
+    Ok(volumes)
+}
+
+/// Returns the directory the tracks of [disc] volume are to be copied to.
+///
+fn volume_dst(dst: &Path, disc: Disc) -> PathBuf {
+    match disc {
+        Some((volume, _)) if !flag("volume-roots") => dst.join(format!("Volume {:02}", volume)),
+        _ => dst.to_path_buf(),
+    }
+}
+
+/// Gets the [disc] volume ready to receive tracks; if [swap] is true,
+/// the previous volume is complete, and it may be time to swap media.
+/// Reports the volume to [progress], if verbose.
+///
+fn volume_enter(dst: &Path, disc: Disc, swap: bool, progress: &mut dyn Progress) {
+    let (volume, volumes_total) = disc.unwrap();
+
+    if flag("v") {
+        progress.warning(&format!(
+            " {} Volume {}/{}: \"{}\"",
+            *BDELIM_ICON,
+            volume,
+            volumes_total,
+            volume_dst(dst, disc).display()
+        ));
+    }
+    if flag("y") {
+        return;
+    }
+    if flag("volume-roots") {
+        if swap {
+            prompt_enter(&format!(
+                "Insert media for volume {}/{}, press Enter to continue",
+                volume, volumes_total
+            ));
+            while !flag("p") && DST_DIR.exists() {
+                prompt_enter(&format!(
+                    "\"{}\" is still there; insert media for volume {}/{}, press Enter",
+                    DST_DIR.display(),
+                    volume,
+                    volumes_total
+                ));
+            }
+            dst_create(progress);
+        }
+    } else {
+        if swap && flag("volume-pause") {
+            prompt_enter(&format!(
+                "Volume {}/{} is next, press Enter to continue",
+                volume, volumes_total
+            ));
+        }
+        dir_create(&volume_dst(dst, disc));
+    }
+}
+
+/// Shows [message], and waits for the user to press Enter.
+///
+fn prompt_enter(message: &str) {
+    print!("\n {} {}... ", *WARNING_ICON, message);
+    io::stdout().flush().unwrap();
+    io::stdin().read_line(&mut String::new()).unwrap();
+}
+
+/// Asks the user a yes/no [question]; returns [default] on a plain Enter.
+///
+fn prompt_yes(question: &str, default: bool) -> bool {
+    print!(
+        "\n {} {} [{}] ",
+        *WARNING_ICON,
+        question,
+        if default { "Y/n" } else { "y/N" }
+    );
+    io::stdout().flush().unwrap();
+
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return default;
+    }
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => true,
+        "n" | "no" => false,
+        _ => default,
+    }
+}
+
+/// The statistics of the audiofiles in a source, collected by tracks_count().
+///
+#[derive(Default)]
+struct Census {
+    suspicious: u64,               // Files with common extensions, which failed to open.
+    suspicious_bytes: u64,         // Sum of their sizes.
+    tracks: u64,                   // Valid audiofiles.
+    bytes: u64,                    // Sum of their sizes.
+    hashes: HashMap<PathBuf, u64>, // Audio payload hashes, if looking for duplicates.
+    props: HashMap<PathBuf, AudioProps>, // Of the valid audiofiles, as far as known.
+    chapters: HashMap<PathBuf, Vec<mp4::Chapter>>, // Of the MP4 tracks with chapters.
+}
+
+impl Census {
+    fn add(&mut self, other: Census) {
+        self.suspicious += other.suspicious;
+        self.suspicious_bytes += other.suspicious_bytes;
+        self.tracks += other.tracks;
+        self.bytes += other.bytes;
+        self.hashes.extend(other.hashes);
+        self.props.extend(other.props);
+        self.chapters.extend(other.chapters);
+    }
+}
+
+/// Returns the statistics of all the audiofiles in [dir] and its subdirectories.
+///
+fn tracks_count(dir: &Path, progress: &mut dyn Progress, log: &mut Vec<String>) -> Census {
+    fn log_name_v(p: &Path) -> String {
+        let (icon, stamp) = match p.metadata().unwrap().created() {
+            Ok(date) => (*BDELIM_ICON, DateTime::<Utc>::from(date)),
+            Err(_) => match p.metadata().unwrap().modified() {
+                Ok(date) => (*ODELIM_ICON, DateTime::<Utc>::from(date)),
+                Err(_) => (
+                    *INVALID_ICON,
+                    DateTime::<Utc>::from(std::time::SystemTime::now()),
+                ),
+            },
+        };
+        format!(
+            "{}{} {}  {} {}",
+            &stamp.date_naive().to_string()[..10],
+            icon,
+            p.strip_prefix(env::current_dir().unwrap())
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            *COLUMN_ICON,
+            human_fine(p.metadata().unwrap().len()),
+        )
+    }
+    fn mismatch_report(p: &Path, log: &mut Vec<String>) {
+        if let Some((container, ext)) = sniff::ext_mismatch(p) {
+            log::warn!("Extension mismatch: \"{}\" is {}", p.display(), container);
+            log.push(format!(
+                " {} {}  {} {} inside; {}",
+                *WARNING_ICON,
+                log_name_v(p),
+                *COLUMN_ICON,
+                container,
+                if flag("fix-ext") {
+                    format!("to be copied as .{}", ext)
+                } else {
+                    format!("should be .{} (see --fix-ext)", ext)
+                }
+            ));
+        }
+    }
+    fn props_collect(p: &Path, census: &mut Census) {
+        if let Some(props) = audio_props(p) {
+            log::debug!(
+                "Audio: \"{}\": {}s, {}kbps, {}Hz, {} channel(s)",
+                p.display(),
+                props.seconds,
+                props.bitrate,
+                props.sample_rate,
+                props.channels
+            );
+            census.props.insert(p.to_path_buf(), props);
+        }
+    }
+    fn chapters_collect(p: &Path, census: &mut Census) {
+        let chapters = chapters_of(p);
+
+        if !chapters.is_empty() {
+            census.chapters.insert(p.to_path_buf(), chapters);
+        }
+    }
+    fn audio_hash(p: &Path, census: &mut Census) {
+        if flag("duplicates") {
+            if let Ok(hash) = check::payload_hash(p) {
+                census.hashes.insert(p.to_path_buf(), hash);
+            }
+        }
+    }
+    fn suspicious_report(p: &Path, progress: &mut dyn Progress, log: &mut Vec<String>) -> u64 {
+        let bytes = p.metadata().unwrap().len();
+        let reason = sniff::diagnose(p);
+
+        progress.suspicious(p, bytes, &reason);
+        log::warn!(
+            "Suspicious, {}: \"{}\": {}",
+            *SUSPICIOUS_FATE,
+            p.display(),
+            reason
+        );
+        log.push(format!(
+            " {} {}  {} {}",
+            *SUSPICIOUS_ICON,
+            log_name_v(p),
+            *COLUMN_ICON,
+            reason
+        ));
+        bytes
+    }
+
+    let mut census = Census::default();
+
+    if dir.is_file() {
+        if is_audiofile(dir) || is_audiofile_fixed(dir) {
+            mismatch_report(dir, log);
+            props_collect(dir, &mut census);
+            chapters_collect(dir, &mut census);
+            audio_hash(dir, &mut census);
+            census.tracks = 1;
+            census.bytes = dir.metadata().unwrap().len();
+        } else if is_pattern_ok(dir) && is_audiofile_ext(dir) {
+            let bytes = suspicious_report(dir, progress, log);
+            census.suspicious = 1;
+            census.suspicious_bytes = bytes;
+            if flag("copy-suspicious") {
+                census.tracks = 1;
+                census.bytes = bytes;
+            }
+        }
+        return census;
+    }
+
+    for p in fs::read_dir(dir)
+        .unwrap()
+        .filter(|r| r.is_ok())
+        .map(|r| r.unwrap().path())
+    {
+        if p.is_dir() {
+            census.add(tracks_count(&p, progress, log));
+        } else if is_audiofile(&p) || is_audiofile_fixed(&p) {
+            census.tracks += 1;
+            census.bytes += p.metadata().unwrap().len();
+            progress.scan_entry(&p);
+            mismatch_report(&p, log);
+            props_collect(&p, &mut census);
+            chapters_collect(&p, &mut census);
+            audio_hash(&p, &mut census);
+        } else if is_pattern_ok(&p) && is_audiofile_ext(&p) {
+            let bytes = suspicious_report(&p, progress, log);
+            census.suspicious += 1;
+            census.suspicious_bytes += bytes;
+            if flag("copy-suspicious") {
+                census.tracks += 1;
+                census.bytes += bytes;
+            }
+        }
+    }
+    census
+}
+
+fn main() {
+    lazy_static::initialize(&ARGS); // Make sure arguments are handled at this point.
+                                    // let _ = *ARGS; // This magic works just as nice.
+    logger_init();
+    lazy_static::initialize(&TRANSCODE_RULES); // Bad rules stop it before the scan.
+
+    if let Some(("undo", undo)) = ARGS.subcommand() {
+        album_undo(Path::new(undo.value_of("journal").unwrap()));
+        return;
+    }
+    if let Some(("check", check)) = ARGS.subcommand() {
+        album_check(Path::new(check.value_of("src").unwrap()));
+        return;
+    }
+
+    if flag("batch") {
+        album_batch();
+        return;
+    }
+
+    let mut log: Vec<String> = Vec::new();
+    let mut progress = progress_create();
+    let (mut src_check_log, srcs) = src_check(progress.as_mut());
+    let (mut mirror_log, mut merge_log) = (vec![], vec![]);
+    let mut tmp_dirs = vec![]; // Keep the directories!
+    let mut origins = HashMap::new();
+    let (mut listed, mut list_log) = (Census::default(), vec![]);
+    let srcs: Vec<PathBuf> = srcs
+        .iter()
+        .filter(|src| {
+            let is_listed = flag("c") && src.is_file() && archive::kind(src).is_some();
+            if is_listed {
+                listed.add(src_list(src, &mut list_log));
+            }
+            !is_listed
+        })
+        .map(|src| {
+            let (unpack_dir, src) = src_unpack(src);
+            let (mut src_mirror_log, mirror_dir, src) = src_mirror(&src, &mut origins);
+            let (mut src_merge_log, merge_dir, src) = if flag("merge") {
+                album_merge(&src, &mut origins)
+            } else {
+                (vec![], None, src)
+            };
+            mirror_log.append(&mut src_mirror_log);
+            merge_log.append(&mut src_merge_log);
+            tmp_dirs.extend([unpack_dir, mirror_dir, merge_dir].into_iter().flatten());
+            src
+        })
+        .collect();
+
+    let now = Instant::now();
+
+    let mut census = Census::default();
+    progress.scan_start();
+    for src in &srcs {
+        census.add(tracks_count(src, progress.as_mut(), &mut log));
+    }
+    census.add(listed); // The archives, if counting.
+
+    log.sort_unstable(); // Suspicious files only, sorting them by date.
+    let skipped = if flag("duplicates") {
+        let (dups, dups_bytes, skipped) = duplicates_resolve(&srcs, &census.hashes, &mut log);
+        if sval("duplicates") == "skip" {
+            census.tracks -= dups;
+            census.bytes -= dups_bytes;
+        }
+        skipped
+    } else {
+        HashSet::new()
+    };
+    progress.scan_done(census.tracks, census.bytes);
+    log.append(&mut list_log);
+    log.append(&mut mirror_log);
+    log.append(&mut merge_log);
+    log.append(&mut src_check_log);
+
+    // First pass through the source done, statistics collected.
+
+    if flag("c") {
+        if flag("chapters") {
+            for (path, _) in srcs_walk(&srcs).filter(|(p, _)| !skipped.contains(p)) {
+                if let Some(chapters) = census.chapters.get(&path) {
+                    progress.chapters(&path, chapters);
+                }
+            }
+        }
+        progress.counted(
+            census.tracks,
+            census.bytes,
+            duration_total(&census, &skipped),
+            now.elapsed().as_secs_f64(),
+        );
+
+        // Statistics reported, nothing else to be done.
+    } else {
+        if !flag("y") {
+            journal_open(&mut log);
+        }
+        album_copy(
+            &now,
+            &srcs,
+            &dst_create(progress.as_mut()),
+            &census,
+            &skipped,
+            &origins,
+            progress.as_mut(),
+            &mut log,
+        );
+
+        // Second pass through the source done, all the tracks, if any, copied to destination.
+    }
+    for s in log {
+        progress.warning(&s);
+    }
+    if census.suspicious > 0 {
+        progress.warning(&format!(
+            " {} Suspicious, {}: {} file(s); Volume: {}",
+            *RSUSP_ICON,
+            *SUSPICIOUS_FATE,
+            census.suspicious,
+            human_fine(census.suspicious_bytes)
+        ));
+    }
+
+    // Final report done.
+}
+
+/// Returns true, if there is a CUE sheet in [dir], or in its subdirectories,
+/// if [recursive].
+///
+fn cue_sheet_any(dir: &Path, recursive: bool) -> bool {
+    fs::read_dir(dir).is_ok_and(|entries| {
+        entries.filter_map(|e| e.ok()).map(|e| e.path()).any(|p| {
+            if p.is_dir() {
+                recursive && cue_sheet_any(&p, true)
+            } else {
+                has_ext_of(p.to_str().unwrap(), "CUE")
+            }
+        })
+    })
+}
+
+/// Returns the audio file in [dir], the [name] of a CUE sheet FILE entry
+/// refers to; rips are often re-encoded, leaving the sheet behind, so
+/// the same stem with another audio extension will do.
+///
+fn cue_file_find(dir: &Path, name: &str) -> Option<PathBuf> {
+    let path = dir.join(name);
+
+    if path.is_file() {
+        return Some(path);
+    }
+    KNOWN_EXTENSIONS
+        .iter()
+        .flat_map(|ext| [ext.to_string(), ext.to_lowercase()])
+        .map(|ext| path.with_extension(ext))
+        .find(|p| p.is_file())
+}
+
+/// Returns the path of the [number] piece of a file being split into [to],
+/// named by its [title], if any, [width] digits at least.
+///
+fn piece_path(to: &Path, number: u32, width: usize, title: Option<&str>, ext: &str) -> PathBuf {
+    let name = format!(
+        "{:01$} - {2}",
+        number,
+        width.max(2),
+        title.unwrap_or_default().replace('/', "-")
+    );
+
+    to.join(format!("{}.{}", name.trim_end_matches([' ', '-']), ext))
+}
+
+/// Cuts the [path] audio file into the tracks of the [file] entry of
+/// the [sheet], into the [to] directory, tagging them from the sheet.
+/// Returns the tracks.
+///
+fn cue_split(
+    path: &Path,
+    sheet: &cue::Sheet,
+    file: &cue::File,
+    to: &Path,
+) -> io::Result<Vec<PathBuf>> {
+    let container = sniff::sniff(path)?;
+    let ext = match container {
+        sniff::Container::Mpeg => "mp3",
+        sniff::Container::Wav => "wav",
+        sniff::Container::Flac | sniff::Container::Ape => "flac",
+        c => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("cannot cut {}", c),
+            ))
+        }
+    };
+    let width = format!("{}", file.tracks.iter().map(|t| t.number).max().unwrap()).len();
+    let dsts: Vec<PathBuf> = file
+        .tracks
+        .iter()
+        .map(|t| piece_path(to, t.number, width, t.title.as_deref(), ext))
+        .collect();
+    let dst_refs: Vec<&Path> = dsts.iter().map(|p| p.as_path()).collect();
+    let starts: Vec<u64> = file.tracks.iter().map(|t| t.start).collect();
+
+    match container {
+        sniff::Container::Mpeg => cue::mp3_split(path, &starts, &dst_refs)?,
+        sniff::Container::Wav => cue::wav_split(path, &starts, &dst_refs)?,
+        _ => {
+            let rate = audio_props(path).map_or(0, |a| a.sample_rate);
+            if rate == 0 {
+                return Err(io::Error::other("unknown sample rate"));
+            }
+            cue::lossless_split(path, rate, &starts, &dst_refs)?
+        }
+    }
+    for (track, dst) in file.tracks.iter().zip(&dsts) {
+        let Ok(tag_file) = taglib::File::new(dst) else {
+            continue;
+        };
+        let Ok(mut tag) = tag_file.tag() else {
+            continue;
+        };
+
+        tag.set_track(track.number);
+        if let Some(title) = &track.title {
+            tag.set_title(title);
+        }
+        if let Some(performer) = track.performer.as_ref().or(sheet.performer.as_ref()) {
+            tag.set_artist(performer);
+        }
+        if let Some(album) = &sheet.title {
+            tag.set_album(album);
+        }
+        tag_file.save();
+    }
+    Ok(dsts)
+}
+
+/// Cuts the [path] MP4 file into its [chapters], into the [to] directory,
+/// tagging them with the chapter titles. Returns the pieces.
+///
+fn chapters_split(path: &Path, chapters: &[mp4::Chapter], to: &Path) -> io::Result<Vec<PathBuf>> {
+    let ext = path.extension().unwrap().to_string_lossy().to_lowercase();
+    let width = format!("{}", chapters.len()).len();
+    let dsts: Vec<PathBuf> = chapters
+        .iter()
+        .enumerate()
+        .map(|(i, c)| piece_path(to, i as u32 + 1, width, Some(&c.title), &ext))
+        .collect();
+    let dst_refs: Vec<&Path> = dsts.iter().map(|p| p.as_path()).collect();
+
+    mp4::split(path, chapters, &dst_refs)?;
+    for (i, (chapter, dst)) in chapters.iter().zip(&dsts).enumerate() {
+        let Ok(tag_file) = taglib::File::new(dst) else {
+            continue;
+        };
+        let Ok(mut tag) = tag_file.tag() else {
+            continue;
+        };
+
+        tag.set_track(i as u32 + 1);
+        tag.set_title(&chapter.title);
+        tag_file.save();
+    }
+    Ok(dsts)
+}
+
+/// Mirrors the [dir] directory of the album into [to]: the audio files,
+/// which CUE sheets cut into tracks, and the chapter books, if asked to,
+/// are split, if possible, the other files (or just the [only] one, if any)
+/// are linked. The pieces and the links go to [origins], each with the file
+/// it was cut from, or links to. Returns the count of the files split.
+///
+fn src_mirror_dir(
+    dir: &Path,
+    to: &Path,
+    only: Option<&Path>,
+    origins: &mut HashMap<PathBuf, PathBuf>,
+    log: &mut Vec<String>,
+) -> u64 {
+    let mut split: HashSet<PathBuf> = HashSet::new();
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|rd| rd.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+        .unwrap_or_default();
+
+    entries.sort();
+    fs::create_dir_all(to).unwrap();
+    for cue_path in entries
+        .iter()
+        .filter(|p| !flag("ignore-cue") && has_ext_of(p.to_str().unwrap(), "CUE"))
+    {
+        let sheet = match fs::read(cue_path).map(|b| cue::parse(&cue::decode(&b))) {
+            Ok(Ok(sheet)) => sheet,
+            Ok(Err(e)) => {
+                log::warn!("Bad CUE sheet \"{}\": {}", cue_path.display(), e);
+                log.push(format!(
+                    " {} Bad CUE sheet \"{}\": {}",
+                    *WARNING_ICON,
+                    cue_path.display(),
+                    e
+                ));
+                continue;
+            }
+            Err(_) => continue,
+        };
+        for file in sheet.files.iter().filter(|f| f.tracks.len() > 1) {
+            let Some(path) = cue_file_find(dir, &file.name) else {
+                continue;
+            };
+            if only.is_some_and(|o| o != path) || split.contains(&path) {
+                continue;
+            }
+            match cue_split(&path, &sheet, file, to) {
+                Ok(pieces) => {
+                    // APE, and such come out as FLAC.
+                    let encoded = pieces
+                        .first()
+                        .and_then(|p| p.extension())
+                        .filter(|ext| !has_ext_of(path.to_str().unwrap(), &ext.to_string_lossy()))
+                        .map_or("".to_string(), |ext| {
+                            format!(", encoded as .{}", ext.to_string_lossy())
+                        });
+                    log::info!(
+                        "CUE: \"{}\": {} track(s){}",
+                        path.display(),
+                        pieces.len(),
+                        encoded
+                    );
+                    log.push(format!(
+                        " {} Split by CUE sheet: \"{}\"  {} {} track(s){}",
+                        *DONE_ICON,
+                        path.display(),
+                        *COLUMN_ICON,
+                        pieces.len(),
+                        encoded
+                    ));
+                    origins.extend(pieces.into_iter().map(|piece| (piece, path.clone())));
+                    split.insert(path);
+                }
+                Err(e) => {
+                    log::warn!("Failed to split \"{}\": {}", path.display(), e);
+                    log.push(format!(
+                        " {} Failed to split \"{}\" by CUE sheet: {}",
+                        *WARNING_ICON,
+                        path.display(),
+                        e
+                    ));
+                }
+            }
+        }
+    }
+
+    let books: Vec<PathBuf> = entries
+        .iter()
+        .filter(|p| {
+            flag("split-chapters")
+                && p.is_file()
+                && !split.contains(*p)
+                && only.is_none_or(|o| o == *p)
+                && is_pattern_ok(p)
+                && sniff::sniff(p).is_ok_and(|c| c == sniff::Container::Mp4)
+        })
+        .cloned()
+        .collect();
+    for p in &books {
+        let chapters: Vec<mp4::Chapter> = mp4::chapters(p)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|c| !c.is_empty())
+            .collect();
+        if chapters.len() < 2 {
+            continue;
+        }
+        match chapters_split(p, &chapters, to) {
+            Ok(pieces) => {
+                log::info!("Chapters: \"{}\": {} track(s)", p.display(), pieces.len());
+                log.push(format!(
+                    " {} Split by chapters: \"{}\"  {} {} track(s)",
+                    *DONE_ICON,
+                    p.display(),
+                    *COLUMN_ICON,
+                    pieces.len()
+                ));
+                origins.extend(pieces.into_iter().map(|piece| (piece, p.clone())));
+                split.insert(p.clone());
+            }
+            Err(e) => {
+                log::warn!("Failed to split \"{}\": {}", p.display(), e);
+                log.push(format!(
+                    " {} Failed to split \"{}\" by chapters: {}",
+                    *WARNING_ICON,
+                    p.display(),
+                    e
+                ));
+            }
+        }
+    }
+
+    let mut count = split.len() as u64;
+    for p in entries {
+        let name = to.join(p.file_name().unwrap());
+        if p.is_dir() {
+            if only.is_none() {
+                count += src_mirror_dir(&p, &name, None, origins, log);
+            }
+        } else if !split.contains(&p) && only.is_none_or(|o| o == p) {
+            std::os::unix::fs::symlink(&p, &name).unwrap_or_else(|_| {
+                panic!(
+                    "{}Failed to link \"{}\".{}",
+                    *BDELIM_ICON,
+                    p.display(),
+                    *BDELIM_ICON
+                )
+            });
+            origins.insert(name, p);
+        }
+    }
+    count
+}
+
+/// Returns the [src] album with the single-file albums inside cut into
+/// tracks by their CUE sheets, and the chapter books into chapters, if asked
+/// to: a mirror of [src] in a temporary directory, the other files linked;
+/// or [src] itself, if there was nothing to cut (no CUE sheets, to begin with),
+/// or nothing is to be copied.
+/// The mirror entries go to [origins] (see src_mirror_dir()).
+///
+fn src_mirror(
+    src: &Path,
+    origins: &mut HashMap<PathBuf, PathBuf>,
+) -> (Vec<String>, Option<TempDir>, PathBuf) {
+    let mut log = vec![];
+    let is_cut = || {
+        flag("split-chapters")
+            || (!flag("ignore-cue")
+                && match src.parent().filter(|_| src.is_file()) {
+                    Some(dir) => cue_sheet_any(dir, false),
+                    None => cue_sheet_any(src, true),
+                })
+    };
+
+    if flag("c") || flag("y") || !is_cut() {
+        return (log, None, src.to_path_buf());
+    }
+    let tmp_dir = TempDir::new().unwrap();
+    let (dir, only, name) = if src.is_file() {
+        (src.parent().unwrap(), Some(src), src.file_stem().unwrap())
+    } else {
+        (src, None, src.file_name().unwrap_or(OsStr::new("album")))
+    };
+    let mirror = tmp_dir.path().join(name);
+
+    let mut mirrored = HashMap::new();
+
+    if src_mirror_dir(dir, &mirror, only, &mut mirrored, &mut log) > 0 {
+        origins.extend(mirrored);
+        (log, Some(tmp_dir), mirror)
+    } else {
+        (log, None, src.to_path_buf())
+    }
+}
+
+/// Lists the audio files of the [src] archive, nothing unpacked: enough, when
+/// only counting. Returns their count, and total size.
+///
+fn src_list(src: &Path, log: &mut Vec<String>) -> Census {
+    let exts: Vec<String> = KNOWN_EXTENSIONS.iter().map(|e| e.to_lowercase()).collect();
+    let exts: Vec<&str> = exts.iter().map(|e| e.as_str()).collect();
+    let list = archive::list(src, &exts).unwrap_or_else(|e| {
+        panic!(
+            "{}Error while listing \"{}\": {}{}",
+            *BDELIM_ICON,
+            src.display(),
+            e,
+            *BDELIM_ICON
+        )
+    });
+    let census = Census {
+        tracks: list.len() as u64,
+        bytes: list.iter().map(|(_, size)| size).sum(),
+        ..Census::default()
+    };
+
+    log::info!("Listed {} file(s) of \"{}\"", census.tracks, src.display());
+    log.push(format!(
+        " {} Listed, not unpacked: \"{}\"  {} {} audio file(s); Volume: {}",
+        *DONE_ICON,
+        src.display(),
+        *COLUMN_ICON,
+        census.tracks,
+        human_fine(census.bytes)
+    ));
+    census
+}
+
+/// An archive entry, standing in the unpacked source tree as a placeholder:
+/// a sparse file of its length, taking no room; copied straight from
+/// the archive (see file_copy()). What is known of it, is known of the file,
+/// unpacked and checked once, before it was done away with.
+///
+struct Streamed {
+    archive: PathBuf,
+    entry: archive::Entry,
+    props: Option<AudioProps>,
+    chapters: Vec<mp4::Chapter>,
+}
+
+/// Returns [f] of the archive entry, the [path] placeholder stands in for;
+/// None, if it is no placeholder.
+///
+fn streamed<T>(path: &Path, f: impl FnOnce(&Streamed) -> T) -> Option<T> {
+    STREAMED.lock().unwrap().get(path).map(f)
+}
+
+/// Returns true, if the audio files of the [src] archive are to be streamed
+/// (see Streamed): they can be copied out one by one, and nothing needs them
+/// as files before copying: no cutting, merging, measuring, transcoding,
+/// fixing, or looking for duplicates.
+///
+fn is_streamable(src: &Path) -> bool {
+    archive::is_random(src)
+        && ![
+            "merge",
+            "split-chapters",
+            "replaygain",
+            "fix-ext",
+            "duplicates",
+        ]
+        .iter()
+        .any(|name| flag(name))
+        && TRANSCODE_RULES.is_empty()
+        && (flag("ignore-cue") || archive::list(src, &["cue"]).is_ok_and(|l| l.is_empty()))
+}
+
+/// Turns the [path] file, just unpacked from the [entry] of the [src] archive,
+/// into a placeholder (see Streamed), if it is a valid audio file; the rest
+/// stay as they are, to be told about.
+///
+fn entry_stand_in(src: &Path, path: &Path, entry: archive::Entry) -> io::Result<()> {
+    if !is_audiofile(path) {
+        return Ok(());
+    }
+    let stand_in = Streamed {
+        archive: src.to_path_buf(),
+        entry,
+        props: audio_props(path),
+        chapters: chapters_of(path),
+    };
+    let file = fs::OpenOptions::new().write(true).open(path)?;
+    let len = file.metadata()?.len();
+
+    file.set_len(0)?;
+    file.set_len(len)?;
+    STREAMED
+        .lock()
+        .unwrap()
+        .insert(path.to_path_buf(), stand_in);
+    Ok(())
+}
+
+/// Unpacks the audio files and CUE sheets of the [src] archive, if it is one,
+/// entry after entry, into a temporary directory, named after the archive.
+/// Streams them, if it can (see is_streamable()): the tree holds placeholders,
+/// one file at a time unpacked in full, to be checked. Otherwise the files
+/// stay, for the tracks are to be split, merged, or measured as files before
+/// copying (see src_list() for counting).
+/// Returns the temporary directory to be kept, and the new source:
+/// the lone top directory of the archive, if there is one.
+///
+fn src_unpack(src: &Path) -> (Option<TempDir>, PathBuf) {
+    if !src.is_file() || archive::kind(src).is_none() {
+        return (None, src.to_path_buf());
+    }
+    let tmp_dir = TempDir::new().unwrap();
+    let to = tmp_dir.path().join(archive::stem(src).unwrap());
+    let exts: Vec<String> = KNOWN_EXTENSIONS
+        .iter()
+        .map(|e| e.to_lowercase())
+        .chain(["cue".to_string()])
+        .collect();
+    let exts: Vec<&str> = exts.iter().map(|e| e.as_str()).collect();
+    let unpacked = if is_streamable(src) {
+        archive::unpack_each(src, &to, &exts, |path, entry| {
+            entry_stand_in(src, path, entry)
+        })
+        .map(|count| (count, "Streaming"))
+    } else {
+        archive::unpack(src, &to, &exts).map(|count| (count, "Unpacked"))
+    };
+
+    match unpacked {
+        Ok((count, how)) => log::info!("{} {} file(s) of \"{}\"", how, count, src.display()),
+        Err(e) => panic!(
+            "{}Error while unpacking \"{}\": {}{}",
+            *BDELIM_ICON,
+            src.display(),
+            e,
+            *BDELIM_ICON
+        ),
+    }
+    let top: Vec<PathBuf> = fs::read_dir(&to)
+        .unwrap()
+        .flatten()
+        .map(|e| e.path())
+        .collect();
+    let src = match &top[..] {
+        [lone] if lone.is_dir() => lone.clone(),
+        _ => to,
+    };
+    (Some(tmp_dir), src)
+}
+
+/// Returns what has to be the same for the [path] track to be merged with
+/// its neighbours: container, sample rate, channels; None, if it cannot be merged.
+///
+fn merge_key(path: &Path) -> Option<(sniff::Container, u32, u32)> {
+    let container = sniff::sniff(path).ok()?;
+    let props = audio_props(path)?;
+
+    match container {
+        // Chained Ogg streams would get only their first link tagged.
+        sniff::Container::Mpeg | sniff::Container::Flac => (),
+        _ => return None,
+    }
+    Some((container, props.sample_rate, props.channels))
+}
+
+/// Merges the [group] of tracks into [dst], tagged after the first one,
+/// titled [title].
+///
+fn group_merge(group: &[PathBuf], dst: &Path, title: &str) -> io::Result<()> {
+    let srcs: Vec<&Path> = group.iter().map(|p| p.as_path()).collect();
+
+    match sniff::sniff(&group[0])? {
+        sniff::Container::Mpeg => merge::mp3(&srcs, dst)?,
+        sniff::Container::Flac => merge::flac(&srcs, dst)?,
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "cannot merge")),
+    }
+    let first = taglib::File::new(&group[0]).ok();
+    let first_tag = first.as_ref().and_then(|f| f.tag().ok());
+    let tag_file = taglib::File::new(dst).map_err(|_| io::Error::other("cannot tag"))?;
+    let mut tag = tag_file.tag().map_err(|_| io::Error::other("cannot tag"))?;
+
+    tag.set_title(title);
+    if let Some(artist) = first_tag.as_ref().and_then(|t| t.artist()) {
+        tag.set_artist(&artist);
+    }
+    if let Some(album) = first_tag.as_ref().and_then(|t| t.album()) {
+        tag.set_album(&album);
+    }
+    tag_file.save();
+    Ok(())
+}
+
+/// Returns the [src] album with its consecutive tracks merged into longer ones,
+/// up to the --merge duration, or a track per source directory: a flat mirror
+/// of [src] in a temporary directory, the tracks left alone linked; or [src]
+/// itself, if there was nothing to merge, or nothing is to be copied.
+/// The mirror entries go to [origins] (see src_mirror_dir()), the merged tracks
+/// with the first track of the group.
+///
+fn album_merge(
+    src: &Path,
+    origins: &mut HashMap<PathBuf, PathBuf>,
+) -> (Vec<String>, Option<TempDir>, PathBuf) {
+    lazy_static! {
+        static ref TARGET: Option<u64> = if sval("merge") == "dir" {
+            None
+        } else {
+            Some(
+                duration_parse(sval("merge"))
+                    .expect("Merge target must be like 30m, 1h30m, or dir!"),
+            )
+        };
+    }
+    let mut log = vec![];
+
+    if flag("c") || flag("y") {
+        return (log, None, src.to_path_buf());
+    }
+    let mut order: Vec<PathBuf> = dir_walk(src, vec![]).map(|(p, _)| p).collect();
+    let mut groups: Vec<Vec<PathBuf>> = vec![];
+    let mut key_last = None;
+    let mut seconds = 0;
+
+    if flag("r") {
+        order.reverse(); // Back to the natural order.
+    }
+    for p in order {
+        let key = merge_key(&p);
+        let length = audio_props(&p).map_or(0, |a| a.seconds as u64);
+        let fits = key.is_some()
+            && key == key_last
+            && groups.last().is_some_and(|g| match *TARGET {
+                Some(target) => seconds + length <= target,
+                None => g[0].parent() == p.parent(),
+            });
+
+        if fits {
+            groups.last_mut().unwrap().push(p);
+            seconds += length;
+        } else {
+            groups.push(vec![p]);
+            seconds = length;
+        }
+        key_last = key;
     }
-    fn step_collect(step: &[PathBuf]) -> PathBuf {
-        step.iter().collect()
+    let tracks: usize = groups.iter().map(|g| g.len()).sum();
+    if groups.len() == tracks {
+        return (log, None, src.to_path_buf());
     }
 
-    fn step_nop_create_dir(_dst: &Path, _step: &Path) {}
-
-    fn step_create_dir(dst: &Path, step: &Path) {
-        let dst_dir = dst.join(step);
-        fs::create_dir_all(&dst_dir).unwrap_or_else(|_| {
+    let tmp_dir = TempDir::new().unwrap();
+    let mirror = tmp_dir
+        .path()
+        .join(src.file_name().unwrap_or(OsStr::new("album")));
+    let width = format!("{}", groups.len()).len();
+    let link = |from: &Path, to: &Path| {
+        std::os::unix::fs::symlink(from, to).unwrap_or_else(|_| {
             panic!(
-                "{}Error while creating \"{}\" directory.{}",
-                BDELIM_ICON,
-                &dst_dir.to_str().unwrap(),
-                BDELIM_ICON
+                "{}Failed to link \"{}\".{}",
+                *BDELIM_ICON,
+                from.display(),
+                *BDELIM_ICON
             )
-        });
-    }
-
-    #[allow(clippy::ptr_arg)]
-    fn file_nop_copytags(_ii: u64, _src: &Path, _dst: &Path, _log: &mut Vec<String>) -> u64 {
-        0
-    }
+        })
+    };
 
-    fn file_copytags(ii: u64, src: &Path, dst: &Path, log: &mut Vec<String>) -> u64 {
-        let mut dst_bytes: u64 = 0;
+    let mut mirrored: Vec<(PathBuf, &PathBuf)> = vec![]; // (Entry, track it stands for).
 
-        if dst.is_file() {
-            log.push(format!(
-                " {} File \"{}\" already copied. Review your options.",
-                WARNING_ICON,
-                &dst.file_name().unwrap().to_str().unwrap()
-            ));
+    fs::create_dir_all(&mirror).unwrap();
+    for (i, group) in groups.iter().enumerate() {
+        let first = &group[0];
+        let ext = first.extension().unwrap().to_string_lossy().to_lowercase();
+        let name = if TARGET.is_none() && group.len() > 1 {
+            first.parent().unwrap().file_name().unwrap()
         } else {
-            file_copy_and_set_tags_via_tmp(ii, src, dst);
-            dst_bytes = dst.metadata().unwrap().len();
+            first.file_stem().unwrap()
         }
-        dst_bytes
-    }
+        .to_string_lossy()
+        .to_string();
+        let dst = mirror.join(format!("{:01$} - {2}.{3}", i + 1, width, name, ext));
 
-    fn out_track_verbose(
-        ii: u64,
-        width: usize,
-        tracks_total: u64,
-        path: &str,
-        dst_bytes: u64,
-        src_bytes: u64,
-    ) {
-        print!(
-            "{:1$}/{2} {3} {4}",
-            ii, width, tracks_total, COLUMN_ICON, path,
-        );
-        if dst_bytes != src_bytes {
-            if dst_bytes == 0 {
-                print!("  {} {}", COLUMN_ICON, human_fine(src_bytes));
-            } else {
-                let growth = dst_bytes as i64 - src_bytes as i64;
+        if group.len() == 1 {
+            link(first, &dst);
+            mirrored.push((dst, first));
+            continue;
+        }
+        match group_merge(group, &dst, &name) {
+            Ok(_) => {
+                log::info!("Merged {} track(s) into \"{}\"", group.len(), name);
+                mirrored.push((dst, first));
+            }
+            Err(e) => {
+                log::warn!("Failed to merge into \"{}\": {}", name, e);
+                log.push(format!(
+                    " {} Failed to merge {} track(s) into \"{}\": {}; copied as they are",
+                    *WARNING_ICON,
+                    group.len(),
+                    name,
+                    e
+                ));
+                fs::remove_file(&dst).ok();
+                for (j, p) in group.iter().enumerate() {
+                    let name = p.file_name().unwrap().to_string_lossy();
+                    let entry =
+                        mirror.join(format!("{:01$}-{2:03} {3}", i + 1, width, j + 1, name));
 
-                print!("  {} {:+}", COLUMN_ICON, growth);
+                    link(p, &entry);
+                    mirrored.push((entry, p));
+                }
             }
         }
-        println!();
     }
-    fn out_track_terse(
-        _ii: u64,
-        _width: usize,
-        _tracks_total: u64,
-        _path: &str,
-        _dst_bytes: u64,
-        _src_bytes: u64,
-    ) {
-        print!(".");
-        io::stdout().flush().unwrap();
+    for (entry, p) in mirrored {
+        let origin = origins.get(p).cloned().unwrap_or_else(|| p.clone());
+        origins.insert(entry, origin);
     }
+    log.push(format!(
+        " {} Merged: {} track(s) into {}",
+        *DONE_ICON,
+        tracks,
+        groups.len()
+    ));
+    (log, Some(tmp_dir), mirror)
+}
 
-    lazy_static! {
-        static ref STEP_COLLECT: fn(&[PathBuf]) -> PathBuf = if flag("t") {
-            step_collect
-        } else {
-            step_flat_collect
-        };
-        static ref STEP_CREATE_DIR: fn(&Path, &Path) = if flag("t") && !flag("y") {
-            step_create_dir
-        } else {
-            step_nop_create_dir
-        };
-        static ref FILE_COPYTAGS: fn(u64, &Path, &Path, &mut Vec<String>) -> u64 = if flag("y") {
-            file_nop_copytags
-        } else {
-            file_copytags
-        };
-        static ref OUT_TRACK: fn(u64, usize, u64, &str, u64, u64) = if flag("v") {
-            out_track_verbose
-        } else {
-            out_track_terse
-        };
-    }
+/// Returns the [path] relative to the first of the [srcs] it is in.
+///
+fn src_relative<'a>(srcs: &[PathBuf], path: &'a Path) -> &'a Path {
+    srcs.iter()
+        .find_map(|src| path.strip_prefix(src).ok())
+        .unwrap_or(path)
+}
+
+/// Returns the tracks of the walk [order] (backwards, if [reversed]) with
+/// the same audio as some track before them in the natural order (see
+/// the [hashes]), each paired with the first such track.
+///
+fn duplicates_find(
+    order: &[PathBuf],
+    reversed: bool,
+    hashes: &HashMap<PathBuf, u64>,
+) -> Vec<(PathBuf, PathBuf)> {
+    let mut first: HashMap<u64, &PathBuf> = HashMap::new();
+    let mut duplicates = vec![];
+    let natural: Box<dyn Iterator<Item = &PathBuf>> = if reversed {
+        Box::new(order.iter().rev())
+    } else {
+        Box::new(order.iter())
+    };
 
-    let stride: PathBuf = STEP_COLLECT(step);
-    STEP_CREATE_DIR(dst, &stride);
-    let dst_file = dst
-        .join(&stride)
-        .join(track_decorate(ii, src_file, step, width));
+    for p in natural {
+        let Some(hash) = hashes.get(p) else { continue };
 
-    OUT_TRACK(
-        ii,
-        width,
-        tracks_total,
-        dst_file.to_str().unwrap(),
-        FILE_COPYTAGS(ii, src_file, &dst_file, log),
-        src_file.metadata().unwrap().len(),
-    );
+        match first.get(hash) {
+            Some(original) => duplicates.push(((*original).clone(), p.clone())),
+            None => {
+                first.insert(*hash, p);
+            }
+        }
+    }
+    duplicates
 }
 
-/// Copies all the valid tracks to their destination, according to
-/// the options and GlobalState.
+/// Finds the tracks of the [srcs] albums with the same audio as some track before
+/// them (see the [hashes], collected by tracks_count()), and reports them.
+/// Returns the count and the total size of the duplicates, and the ones
+/// to be skipped, if asked to.
 ///
-fn album_copy(
-    now: &Instant,
-    src: &Path,
-    dst: &Path,
-    tracks_total: u64,
-    bytes_total: u64,
+fn duplicates_resolve(
+    srcs: &[PathBuf],
+    hashes: &HashMap<PathBuf, u64>,
     log: &mut Vec<String>,
-) {
-    fn out_start_terse() {
-        print!(" {} ", START_ICON);
-        io::stdout().flush().unwrap();
-    }
-    fn out_nop() {}
-
-    fn out_tail_terse() {
-        println!(" {}", STOP_ICON);
-    }
+) -> (u64, u64, HashSet<PathBuf>) {
+    let order: Vec<PathBuf> = srcs_walk(srcs).map(|(p, _)| p).collect();
+    let mut skipped = HashSet::new();
+    let mut bytes = 0;
+    let mut count = 0;
 
-    fn out_done(tracks_total: u64, bytes_total: u64, time_elapsed: f64) {
-        println!(
-            " {} Done ({}, {}; {:.1}s).",
-            DONE_ICON,
-            tracks_total,
-            human_fine(bytes_total),
-            time_elapsed,
+    for (original, p) in duplicates_find(&order, flag("r"), hashes) {
+        log::warn!(
+            "Same audio as \"{}\": \"{}\"",
+            original.display(),
+            p.display()
         );
+        log.push(format!(
+            " {} Same audio as \"{}\": \"{}\"{}",
+            *WARNING_ICON,
+            src_relative(srcs, &original).display(),
+            src_relative(srcs, &p).display(),
+            if sval("duplicates") == "skip" {
+                "; skipped"
+            } else {
+                ""
+            }
+        ));
+        count += 1;
+        bytes += p.metadata().map_or(0, |m| m.len());
+        if sval("duplicates") == "skip" {
+            skipped.insert(p);
+        }
     }
+    (count, bytes, skipped)
+}
 
-    fn tracks_range(range: u64) -> Box<dyn Iterator<Item = u64>> {
-        Box::new(1_u64..=range)
-    }
-    fn tracks_range_rev(range: u64) -> Box<dyn Iterator<Item = u64>> {
-        Box::new((1_u64..=range).rev())
-    }
+/// Returns what is known about the [path] track of the [src] album, for checking.
+///
+fn track_info(src: &Path, path: &Path) -> check::Info {
+    let file = taglib::File::new(path).ok();
+    let tag = file.as_ref().and_then(|f| f.tag().ok());
+    let props = audio_props(path).unwrap_or_default();
 
-    lazy_static! {
-        static ref OUT_START: fn() = if flag("v") { out_nop } else { out_start_terse };
-        static ref OUT_TAIL: fn() = if flag("v") { out_nop } else { out_tail_terse };
-        static ref OUT_DONE: fn(u64, u64, f64) = out_done;
-        static ref RANGE: fn(u64) -> Box<dyn Iterator<Item = u64>> = if flag("r") {
-            tracks_range_rev
-        } else {
-            tracks_range
-        };
+    check::Info {
+        path: path.strip_prefix(src).unwrap_or(path).to_path_buf(),
+        track: tag.as_ref().and_then(|t| t.track()).filter(|n| *n > 0),
+        artist: tag.as_ref().and_then(|t| t.artist()).unwrap_or_default(),
+        album: tag.as_ref().and_then(|t| t.album()).unwrap_or_default(),
+        format: sniff::sniff(path).map_or("?".to_string(), |c| c.to_string()),
+        bitrate: props.bitrate,
+        cbr: check::is_cbr(path).unwrap_or(false),
+        sample_rate: props.sample_rate,
+        seconds: props.seconds,
+        hash: check::payload_hash(path).ok(),
     }
+}
 
-    if tracks_total < 1 {
-        println!(
-            " {} No audio files found at \"{}\"",
-            WARNING_ICON,
-            src.display()
-        );
+/// Checks the [src] album, reports the problems found; exits with 1, if any.
+///
+fn album_check(src: &Path) {
+    let src = src.canonicalize().unwrap_or_else(|_| {
+        panic!(
+            "{}File or directory \"{}\" does not exist.{}",
+            *BDELIM_ICON,
+            src.display(),
+            *BDELIM_ICON
+        )
+    });
+    let now = Instant::now();
+    let mut progress = progress_create();
+    let mut log: Vec<String> = Vec::new();
+
+    let census = tracks_count(&src, progress.as_mut(), &mut log);
+
+    progress.scan_done(census.tracks, census.bytes);
+
+    let infos: Vec<check::Info> = dir_walk(&src, vec![])
+        .map(|(path, _)| track_info(&src, &path))
+        .collect();
+    let problems = check::problems(&infos);
+
+    log.sort_unstable();
+    for s in &log {
+        progress.warning(s);
+    }
+    for p in &problems {
+        progress.warning(&format!(" {} {}", *WARNING_ICON, p));
+    }
+    progress.checked(
+        census.tracks,
+        census.suspicious + problems.len() as u64,
+        now.elapsed().as_secs_f64(),
+    );
+    if census.suspicious > 0 || !problems.is_empty() {
         exit(1);
     }
+}
 
-    OUT_START();
+/// Returns the albums of the batch: the subdirectories of [src] in sort order,
+/// or the sources, listed in the [src] file, relative to it; blank lines,
+/// and #comments skipped.
+///
+fn batch_albums(src: &Path) -> Vec<PathBuf> {
+    if src.is_file() {
+        let dir = src.parent().unwrap();
 
-    let width = format!("{}", tracks_total).len();
+        fs::read_to_string(src)
+            .unwrap_or_else(|_| {
+                panic!(
+                    "{}Error while reading \"{}\".{}",
+                    *BDELIM_ICON,
+                    src.display(),
+                    *BDELIM_ICON
+                )
+            })
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| dir.join(line))
+            .collect()
+    } else {
+        let mut dirs = fs_entries(src, true).unwrap();
 
-    for (i, (src, step)) in zip_eq(RANGE(tracks_total), dir_walk(src, [].to_vec())) {
-        track_copy(i, &src, &step, dst, width, tracks_total, log);
+        if flag("x") {
+            dirs.sort_unstable();
+        } else {
+            sort_path_slice(&mut dirs);
+        }
+        dirs
     }
-
-    OUT_TAIL();
-    OUT_DONE(tracks_total, bytes_total, now.elapsed().as_secs_f64());
 }
 
-/// Returns the statistics of all the audiofiles in [dir] and its subdirectories
-/// in the form of a tuple:
-/// (
-/// Full count of the files with common extensions, which failed to open (suspicious),
-/// Sum of all the sizes of the suspicious audiofiles/tracks (suspicious bytes)
-/// Full count of the valid audiofiles (tracks),
-/// Sum of all the sizes of the valid audiofiles (bytes)
-/// )
+/// Returns the command line arguments of the run, copying the [album] of the batch,
+/// [index] (from 1), numbered [number], if asked to: the options of this run,
+/// as given by [args], save the batch ones; the journal file, if any,
+/// suffixed with the [index].
 ///
-fn tracks_count(
-    dir: &Path,
-    spinner: &mut dyn Spinner,
-    log: &mut Vec<String>,
-) -> (u64, u64, u64, u64) {
-    fn log_name_v(p: &Path) -> String {
-        let (icon, stamp) = match p.metadata().unwrap().created() {
-            Ok(date) => (BDELIM_ICON, DateTime::<Utc>::from(date)),
-            Err(_) => match p.metadata().unwrap().modified() {
-                Ok(date) => (ODELIM_ICON, DateTime::<Utc>::from(date)),
-                Err(_) => (
-                    INVALID_ICON,
-                    DateTime::<Utc>::from(std::time::SystemTime::now()),
-                ),
-            },
+fn batch_album_args(
+    args: &ArgMatches,
+    album: &Path,
+    index: usize,
+    number: Option<i64>,
+) -> Vec<String> {
+    let command = args_command();
+    let mut album_args = vec![];
+
+    for arg in command.get_arguments() {
+        let id = arg.get_id();
+
+        if arg.is_positional() || args.occurrences_of(id) == 0 || id.starts_with("batch") {
+            continue;
+        }
+        if id == "b" && number.is_some() {
+            continue;
+        }
+        let name = match arg.get_long() {
+            Some(long) => format!("--{}", long),
+            None => format!("-{}", arg.get_short().unwrap()),
         };
-        format!(
-            "{}{} {}  {} {}",
-            &stamp.date_naive().to_string()[..10],
-            icon,
-            p.strip_prefix(env::current_dir().unwrap())
-                .unwrap()
-                .to_str()
-                .unwrap(),
-            COLUMN_ICON,
-            human_fine(p.metadata().unwrap().len()),
-        )
+        if id == "journal" {
+            album_args.push(format!("{}={}.{}", name, args.value_of(id).unwrap(), index));
+        } else if arg.is_takes_value_set() {
+            album_args.extend(
+                args.values_of(id)
+                    .unwrap()
+                    .map(|v| format!("{}={}", name, v)),
+            );
+        } else {
+            album_args.extend((0..args.occurrences_of(id)).map(|_| name.clone()));
+        }
     }
-    fn log_name(p: &Path) -> String {
-        p.file_name().unwrap().to_str().unwrap().to_string()
+    if let Some(number) = number {
+        album_args.push(format!("--album-num={}", number));
     }
+    album_args.push("--".to_string());
+    album_args.push(album.to_str().unwrap().to_string());
+    album_args.push(args.value_of("dst-dir").unwrap().to_string());
+    album_args
+}
 
-    if dir.is_file() {
-        if is_audiofile(dir) {
-            return (0, 0, 1, dir.metadata().unwrap().len());
-        } else if is_pattern_ok(dir) && is_audiofile_ext(dir) {
-            log.push(format!(" {} {}", SUSPICIOUS_ICON, log_name_v(dir)));
-            return (1, dir.metadata().unwrap().len(), 0, 0);
-        }
-        return (0, 0, 0, 0);
+/// Copies the albums of the batch (see batch_albums()) one after another,
+/// each by a run of its own, going on past the failed ones; sums the results up.
+///
+fn album_batch() {
+    let srcs = pvals("src");
+    if srcs.len() != 1 {
+        progress_create().error(&format!(
+            " {} Batch mode takes one <src>: a directory, or a list file.",
+            *WARNING_ICON
+        ));
+        exit(1);
+    }
+    let albums = batch_albums(&srcs[0]);
+    if albums.is_empty() {
+        progress_create().error(&format!(
+            " {} No albums found at \"{}\"",
+            *WARNING_ICON,
+            srcs[0].display()
+        ));
+        exit(1);
     }
+    let first = if flag("b") { ival("b") } else { 1 };
+    let exe = env::current_exe().unwrap();
+    let mut results = vec![];
 
-    let mut bytes = 0;
-    let mut suspicious = 0;
-    let mut suspicious_bytes = 0;
+    for (i, album) in albums.iter().enumerate() {
+        let number = flag("batch-number").then_some(first + i as i64);
+        let start = Instant::now();
 
-    let tracks = fs::read_dir(dir)
-        .unwrap()
-        .filter(|r| r.is_ok())
-        .map(|r| {
-            let p = r.unwrap().path();
-            if p.is_dir() {
-                let (sc, sb, tc, tb) = tracks_count(&p, spinner, log);
-                suspicious += sc;
-                suspicious_bytes += sb;
-                bytes += tb;
-                tc
-            } else if is_audiofile(&p) {
-                bytes += &p.metadata().unwrap().len();
-                spinner.message(log_name(&p));
-                1
-            } else {
-                if is_pattern_ok(&p) && is_audiofile_ext(&p) {
-                    suspicious += 1;
-                    suspicious_bytes += &p.metadata().unwrap().len();
-                    log.push(format!(" {} {}", SUSPICIOUS_ICON, log_name_v(&p)))
-                }
-                0
-            }
-        })
-        .sum();
+        log::info!("Batch album {}: \"{}\"", i + 1, album.display());
+        let failure = match std::process::Command::new(&exe)
+            .args(batch_album_args(&ARGS, album, i + 1, number))
+            .status()
+        {
+            Ok(status) if status.success() => None,
+            Ok(status) => Some(
+                status
+                    .code()
+                    .map_or("killed".to_string(), |c| format!("exit code {}", c)),
+            ),
+            Err(e) => Some(e.to_string()),
+        };
+        if let Some(e) = &failure {
+            log::warn!("Batch album failed: \"{}\": {}", album.display(), e);
+        }
+        let name = album.file_name().unwrap_or_default().to_string_lossy();
+        let name = match number {
+            Some(n) => format!("{:02}-{}", n, name),
+            None => name.to_string(),
+        };
+        results.push((name, failure, start.elapsed().as_secs()));
+    }
 
-    (suspicious, suspicious_bytes, tracks, bytes)
+    progress_create().batched(&results); // No spinning over the runs' own output.
+    if results.iter().any(|(_, failure, _)| failure.is_some()) {
+        exit(1);
+    }
 }
 
-fn main() {
-    lazy_static::initialize(&ARGS); // Make sure arguments are handled at this point.
-                                    // let _ = *ARGS; // This magic works just as nice.
+/// Starts logging, if asked to: to the log file, if any, otherwise to stderr.
+///
+fn logger_init() {
+    if !flag("log-file") && !flag("log-level") {
+        return;
+    }
+    let path = if flag("log-file") {
+        Some(PathBuf::from(sval("log-file")))
+    } else {
+        None
+    };
 
-    let mut log: Vec<String> = Vec::new();
-    let (mut src_check_log, src) = src_check();
+    logger::init(path.as_deref(), sval("log-level").parse().unwrap()).unwrap_or_else(|e| {
+        panic!(
+            "{}Failed to open the log file \"{}\": {}.{}",
+            *BDELIM_ICON,
+            sval("log-file"),
+            e,
+            *BDELIM_ICON
+        )
+    });
+    log::info!("Started: {:?}", env::args().collect::<Vec<_>>());
+}
 
-    let now = Instant::now();
-    let mut spinner = spin::DaddySpinner::new();
+/// Returns the progress reporter, chosen by the options: the terminal one,
+/// if stdout is a terminal, the plain one otherwise, unless specified;
+/// the silent one, if quiet.
+///
+fn progress_create() -> Box<dyn Progress> {
+    if flag("q") {
+        return Box::new(progress::SilentProgress::new());
+    }
+    match sval("progress") {
+        "terminal" => Box::new(progress::TerminalProgress::new(flag("v"))),
+        "plain" => Box::new(progress::PlainProgress::new(flag("v"))),
+        "json" => Box::new(progress::JsonProgress::new()),
+        "silent" => Box::new(progress::SilentProgress::new()),
+        _ if io::stdout().is_terminal() => Box::new(progress::TerminalProgress::new(flag("v"))),
+        _ => Box::new(progress::PlainProgress::new(flag("v"))),
+    }
+}
 
-    let (suspicious_total, suspicious_bytes_total, tracks_total, bytes_total) =
-        tracks_count(src.as_path(), &mut spinner, &mut log);
+/// Starts journaling the run, so that it can be undone.
+///
+fn journal_open(log: &mut Vec<String>) {
+    let path = if flag("journal") {
+        PathBuf::from(sval("journal"))
+    } else {
+        journal::default_path(&DST_DIR.file_name().unwrap_or_default().to_string_lossy())
+    };
 
-    spinner.stop();
-    log.sort_unstable(); // Suspicious files only, sorting them by date.
-    log.append(&mut src_check_log);
+    match journal::open(&path) {
+        Ok(_) => log.push(format!(
+            " {} Undo: procrustes undo \"{}\"",
+            *BDELIM_ICON,
+            path.display()
+        )),
+        Err(e) => log.push(format!(
+            " {} Failed to open journal \"{}\": {}",
+            *WARNING_ICON,
+            path.display(),
+            e
+        )),
+    }
+}
 
-    // First pass through the source done, statistics collected.
+/// Undoes the run, recorded in the [path] journal.
+///
+fn album_undo(path: &Path) {
+    let mut progress = progress_create();
 
-    if flag("c") {
-        print!(
-            " {} Valid: {} file(s); Volume: {}",
-            if tracks_total > 0 {
-                DONE_ICON
-            } else {
-                WARNING_ICON
-            },
-            tracks_total,
-            human_fine(bytes_total)
-        );
-        if tracks_total > 1 {
-            print!("; Average: {}", human_fine(bytes_total / tracks_total));
+    match journal::undo(path) {
+        Ok((undone, complaints)) => {
+            for c in &complaints {
+                progress.warning(&format!(" {} {}", *WARNING_ICON, c));
+            }
+            progress.undone(undone, complaints.len() as u64);
+        }
+        Err(e) => {
+            progress.error(&format!(
+                " {} Failed to read journal \"{}\": {}",
+                *WARNING_ICON,
+                path.display(),
+                e
+            ));
+            exit(1);
         }
-        println!("; Time: {:.1}s", now.elapsed().as_secs_f64())
+    }
+}
 
-        // Statistics reported, nothing else to be done.
-    } else {
-        album_copy(
-            &now,
-            &src,
-            &dst_create(),
-            tracks_total,
-            bytes_total,
-            &mut log,
-        );
+/// Returns [seconds] as H:MM:SS, or M:SS, if less than an hour.
+///
+fn human_duration(seconds: u64) -> String {
+    let (h, m, s) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
 
-        // Second pass through the source done, all the tracks, if any, copied to destination.
-    }
-    for s in log {
-        println!("{}", s);
-    }
-    if suspicious_total > 0 {
-        println!(
-            " {} Suspicious, skipped: {} file(s); Volume: {}",
-            RSUSP_ICON,
-            suspicious_total,
-            human_fine(suspicious_bytes_total)
-        );
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
     }
-
-    // Final report done.
 }
 
 /// Returns a human readable string representation of [bytes], nicely rounded.
@@ -1059,7 +3820,7 @@ fn human_fine(bytes: u64) -> String {
             (unit, 2) => format!("{:.2}{}", quotient, unit),
             _ => panic!(
                 "{}Fatal error: human_fine(): unexpected decimals count.{}",
-                BDELIM_ICON, BDELIM_ICON
+                *BDELIM_ICON, *BDELIM_ICON
             ),
         };
     }
@@ -1071,10 +3832,64 @@ fn human_fine(bytes: u64) -> String {
     }
     panic!(
         "{}Fatal error: human_fine({}).{}",
-        BDELIM_ICON, bytes, BDELIM_ICON
+        *BDELIM_ICON, bytes, *BDELIM_ICON
     )
 }
 
+/// Returns the number of bytes, represented by [s] in the human_fine() way,
+/// e.g. "700M", "3.5GB", "2g", or just "4096"; None, if [s] makes no sense.
+///
+fn human_parse(s: &str) -> Option<u64> {
+    let s = s.trim().to_uppercase();
+    let s = s.strip_suffix('B').unwrap_or(&s);
+    let (number, exponent) = match s.chars().last()? {
+        'K' => (&s[..s.len() - 1], 1),
+        'M' => (&s[..s.len() - 1], 2),
+        'G' => (&s[..s.len() - 1], 3),
+        'T' => (&s[..s.len() - 1], 4),
+        _ => (s, 0),
+    };
+    let quotient: f64 = number.trim().parse().ok()?;
+
+    if !quotient.is_finite() || quotient < 0.0 {
+        return None;
+    }
+    Some((quotient * 1024.0_f64.powi(exponent)) as u64)
+}
+
+/// Returns the number of seconds, represented by [s], e.g. "30m", "1h30m",
+/// "90s", or just "45" (minutes); None, if [s] makes no sense.
+///
+fn duration_parse(s: &str) -> Option<u64> {
+    let s = s.trim().to_lowercase();
+
+    if let Ok(minutes) = s.parse::<u64>() {
+        return Some(minutes * 60);
+    }
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in s.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'h' | 'm' | 's' if !number.is_empty() => {
+                let n: u64 = number.parse().ok()?;
+                seconds += n * match c {
+                    'h' => 3600,
+                    'm' => 60,
+                    _ => 1,
+                };
+                number.clear();
+            }
+            _ => return None,
+        }
+    }
+    if number.is_empty() && seconds > 0 {
+        Some(seconds)
+    } else {
+        None
+    }
+}
+
 /// Shrinks [s] to the [limit], removing an arbitrary
 /// slice from the middle.
 ///
@@ -1149,19 +3964,99 @@ fn is_audiofile_ext(path: &Path) -> bool {
         .any(|ext| has_ext_of(path.to_str().unwrap(), ext))
 }
 
+/// Returns the transcoding rules, given on the command line.
+///
+fn transcode_rules() -> Vec<transcode::Rule> {
+    ARGS.values_of("transcode")
+        .map_or(vec![], |rules| rules.collect())
+        .into_iter()
+        .map(|r| match transcode::rule_parse(r) {
+            Ok(rule) if KNOWN_EXTENSIONS.contains(&rule.to.to_uppercase().as_str()) => rule,
+            Ok(rule) => panic!(
+                "{}Transcoding to .{} files is not supported.{}",
+                *BDELIM_ICON, rule.to, *BDELIM_ICON
+            ),
+            Err(e) => panic!(
+                "{}Bad transcoding rule: {}.{}",
+                *BDELIM_ICON, e, *BDELIM_ICON
+            ),
+        })
+        .collect()
+}
+
+/// Returns the first transcoding rule, applicable to the [src] file, if any.
+///
+fn transcode_rule(src: &Path) -> Option<&'static transcode::Rule> {
+    TRANSCODE_RULES.iter().find(|rule| {
+        rule.from
+            .iter()
+            .any(|ext| has_ext_of(src.to_str().unwrap(), ext))
+    })
+}
+
 /// Returns true, if [path] is a valid audio file, otherwise false.
 ///
 fn is_audiofile(path: &Path) -> bool {
     if is_pattern_ok(path) {
-        match taglib::File::new(path) {
-            Err(_) => false,
-            Ok(v) => v.tag().is_ok(),
-        }
+        streamed(path, |_| ()).is_some()
+            || match taglib::File::new(path) {
+                Err(_) => false,
+                Ok(v) => v.tag().is_ok(),
+            }
     } else {
         false
     }
 }
 
+/// Returns true, if TagLib reads the [path] file, named with the [ext] extension
+/// instead of its own; probed through a symlink, so named.
+///
+fn is_audiofile_as(path: &Path, ext: &str) -> bool {
+    let (Ok(dir), Ok(target)) = (TempDir::new(), path.canonicalize()) else {
+        return false;
+    };
+    let link = dir.path().join("probe").with_extension(ext);
+
+    std::os::unix::fs::symlink(target, &link).is_ok()
+        && taglib::File::new(&link).is_ok_and(|f| f.tag().is_ok())
+}
+
+/// Returns true, if [path] is not a valid audio file under its own name,
+/// but is one under the name, fixed by --fix-ext, if asked to.
+///
+fn is_audiofile_fixed(path: &Path) -> bool {
+    flag("fix-ext")
+        && is_pattern_ok(path)
+        && is_audiofile_ext(path)
+        && !is_audiofile(path)
+        && sniff::ext_mismatch(path).is_some_and(|(_, ext)| is_audiofile_as(path, &ext))
+}
+
+/// Returns true, if the [dst] copy of [src] is to be tagged: [src] is a valid
+/// audio file, and so is [dst] by its name (renamed by --fix-ext, or transcoded);
+/// otherwise, if [src] is readable under the [dst] extension (see is_audiofile_fixed()).
+///
+fn is_taggable(src: &Path, dst: &Path) -> bool {
+    if is_audiofile(src) {
+        is_audiofile_ext(dst)
+    } else {
+        is_pattern_ok(src)
+            && dst.extension() != src.extension()
+            && dst
+                .extension()
+                .is_some_and(|ext| is_audiofile_as(src, &ext.to_string_lossy()))
+    }
+}
+
+/// Returns true, if [path] is to be copied: a valid audio file (see also
+/// is_audiofile_fixed()), or, if asked to, a suspicious one (to be copied untagged).
+///
+fn is_track(path: &Path) -> bool {
+    is_audiofile(path)
+        || is_audiofile_fixed(path)
+        || (flag("copy-suspicious") && is_pattern_ok(path) && is_audiofile_ext(path))
+}
+
 fn has_ext_of(path: &str, ext: &str) -> bool {
     let p = path.to_uppercase();
     let e = ext.to_uppercase().replace('.', "");
@@ -1234,9 +4129,7 @@ fn initials(authors: &str) -> String {
             }
         }
 
-        if v[0].chars().next().unwrap().is_lowercase()
-            && NOBILIARY_PARTICLES.iter().any(|&x| name == x)
-        {
+        if v[0].chars().next().unwrap().is_lowercase() && NOBILIARY_PARTICLES.contains(&name) {
             return v[0].to_string();
         }
 
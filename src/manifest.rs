@@ -0,0 +1,149 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+/// The file at the destination root, which keeps track of what was copied where,
+/// so that the next run in sync mode knows what to do.
+pub const MANIFEST_NAME: &str = ".procrustes-sync";
+
+/// What is known about a track, copied to destination.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub dst: PathBuf, // Relative to the destination directory.
+    pub src: PathBuf,
+    pub bytes: u64,
+    pub mtime: u64,
+    pub ii: u64,
+    pub disc: String,
+}
+
+impl Record {
+    /// Makes a record of the [src] track (number [ii]), to be found at [dst]
+    /// relative to the destination directory.
+    ///
+    pub fn new(dst: &Path, src: &Path, ii: u64, disc: String) -> Self {
+        let meta = src.metadata().unwrap();
+
+        Self {
+            dst: dst.to_path_buf(),
+            src: src.to_path_buf(),
+            bytes: meta.len(),
+            mtime: meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs()),
+            ii,
+            disc,
+        }
+    }
+
+    fn key(&self) -> (&Path, u64, u64) {
+        (&self.src, self.bytes, self.mtime)
+    }
+}
+
+/// What sync mode is to do with a track.
+///
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Keep,
+    Rename(PathBuf, bool), // (Old relative path, true if the tags are to be refreshed).
+    Copy,
+}
+
+/// Reads the manifest from [dir]; returns the options signature
+/// and the records, or None, if there is no readable manifest.
+///
+pub fn read(dir: &Path) -> Option<(String, Vec<Record>)> {
+    let file = fs::File::open(dir.join(MANIFEST_NAME)).ok()?;
+    let mut lines = BufReader::new(file).lines();
+    let signature = lines.next()?.ok()?.strip_prefix("# ")?.to_string();
+    let mut records = Vec::new();
+
+    for line in lines {
+        let line = line.ok()?;
+        let fields: Vec<&str> = line.split('\t').collect();
+
+        if fields.len() != 6 {
+            return None;
+        }
+        records.push(Record {
+            dst: PathBuf::from(fields[0]),
+            src: PathBuf::from(fields[1]),
+            bytes: fields[2].parse().ok()?,
+            mtime: fields[3].parse().ok()?,
+            ii: fields[4].parse().ok()?,
+            disc: fields[5].to_string(),
+        });
+    }
+    Some((signature, records))
+}
+
+/// Writes the manifest of [records] and the options [signature] to [dir].
+///
+pub fn write(dir: &Path, signature: &str, records: &[Record]) -> io::Result<()> {
+    let mut file = io::BufWriter::new(fs::File::create(dir.join(MANIFEST_NAME))?);
+
+    writeln!(file, "# {}", signature)?;
+    for r in records {
+        writeln!(
+            file,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            r.dst.display(),
+            r.src.display(),
+            r.bytes,
+            r.mtime,
+            r.ii,
+            r.disc
+        )?;
+    }
+    file.flush()
+}
+
+/// Compares [old] records (what is at the destination) with [new] ones
+/// (what should be there). Returns the action for each of the [new] records,
+/// and the relative paths of the [old] tracks, no longer needed.
+///
+pub fn plan(old: &[Record], new: &[Record]) -> (Vec<Action>, Vec<PathBuf>) {
+    let mut unclaimed: HashMap<(&Path, u64, u64), Vec<&Record>> = HashMap::new();
+
+    for r in old.iter().rev() {
+        unclaimed.entry(r.key()).or_default().push(r);
+    }
+
+    let actions = new
+        .iter()
+        .map(
+            |r| match unclaimed.get_mut(&r.key()).and_then(|v| v.pop()) {
+                Some(o) if o.dst == r.dst && o.ii == r.ii && o.disc == r.disc => Action::Keep,
+                Some(o) => Action::Rename(o.dst.clone(), o.ii != r.ii || o.disc != r.disc),
+                None => Action::Copy,
+            },
+        )
+        .collect();
+    let mut stale: Vec<PathBuf> = unclaimed
+        .into_values()
+        .flatten()
+        .map(|r| r.dst.clone())
+        .collect();
+
+    stale.sort_unstable();
+    (actions, stale)
+}
+
+/// Returns true, if some track is to be copied before a track, which is already
+/// there; [actions] being in the copy order. Then the physical order of the files
+/// at the destination no longer follows the track numbers.
+///
+pub fn is_order_broken(actions: &[Action]) -> bool {
+    actions
+        .iter()
+        .skip_while(|a| **a != Action::Copy)
+        .any(|a| *a != Action::Copy)
+}
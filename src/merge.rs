@@ -0,0 +1,266 @@
+use crate::cue;
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+fn bad(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what.to_string())
+}
+
+/// Concatenates the MPEG audio frames of the [srcs] MP3 files into [dst],
+/// leaving the tags and the Xing/Info frames behind. All the frames have to be
+/// of the same bitrate, sample rate, and channel mode, there being no Xing
+/// frame to tell the length of the merged stream otherwise.
+///
+pub fn mp3(srcs: &[&Path], dst: &Path) -> io::Result<()> {
+    let mut out = fs::File::create(dst)?;
+    let mut params = None;
+
+    for src in srcs {
+        let mut start = None;
+        let mut varies = false;
+        let end = cue::mpeg_scan(src, |offset, header, _, _| {
+            // Version, layer, bitrate, sample rate, channel mode.
+            let frame_params = [header[1] & 0xfe, header[2] & 0xfc, header[3] & 0xc0];
+
+            start.get_or_insert(offset);
+            varies |= *params.get_or_insert(frame_params) != frame_params;
+        })?;
+
+        if varies {
+            return Err(bad("bitrate or stream parameters vary"));
+        }
+        let start = start.ok_or_else(|| bad("no audio frames"))?;
+        let mut file = fs::File::open(src)?;
+
+        file.seek(SeekFrom::Start(start))?;
+        io::copy(&mut file.take(end - start), &mut out)?;
+    }
+    Ok(())
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |crc, b| {
+        (0..8).fold(crc ^ b, |c, _| {
+            if c & 0x80 != 0 {
+                (c << 1) ^ 0x07
+            } else {
+                c << 1
+            }
+        })
+    })
+}
+
+fn crc16_update(crc: u16, b: u8) -> u16 {
+    (0..8).fold(crc ^ ((b as u16) << 8), |c, _| {
+        if c & 0x8000 != 0 {
+            (c << 1) ^ 0x8005
+        } else {
+            c << 1
+        }
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, b| crc16_update(crc, *b))
+}
+
+/// Returns [n] coded the way FLAC frame headers code frame and sample numbers.
+///
+pub fn flac_number(n: u64) -> Vec<u8> {
+    if n < 0x80 {
+        return vec![n as u8];
+    }
+    let len = (2..=7).find(|len| n < 1 << (5 * len + 1)).unwrap_or(7);
+    let mut bytes = vec![!(0xff_u8 >> len) | (n >> (6 * (len - 1))) as u8];
+
+    for i in (0..len - 1).rev() {
+        bytes.push(0x80 | (n >> (6 * i)) as u8 & 0x3f);
+    }
+    bytes
+}
+
+/// Returns (header length, number length, block size) of the FLAC frame,
+/// [data] starting with its header, if the header is valid.
+///
+fn flac_frame_header(data: &[u8]) -> Option<(usize, usize, u64)> {
+    if data.len() < 6 || data[0] != 0xff || data[1] & 0xfe != 0xf8 {
+        return None;
+    }
+    let (size_code, rate_code) = (data[2] >> 4, data[2] & 0x0f);
+    if size_code == 0 || rate_code == 15 || data[3] >> 4 >= 11 || data[3] & 0x01 != 0 {
+        return None;
+    }
+    let number_len = match data[4].leading_ones() as usize {
+        0 => 1,
+        len @ 2..=7 => len,
+        _ => return None,
+    };
+    let mut at = 4 + number_len;
+    let block_size = match size_code {
+        1 => 192,
+        2..=5 => 576 << (size_code - 2),
+        6 => {
+            at += 1;
+            *data.get(at - 1)? as u64 + 1
+        }
+        7 => {
+            at += 2;
+            u16::from_be_bytes([*data.get(at - 2)?, *data.get(at - 1)?]) as u64 + 1
+        }
+        _ => 256 << (size_code - 8),
+    };
+    at += match rate_code {
+        12 => 1,
+        13 | 14 => 2,
+        _ => 0,
+    };
+    if *data.get(at)? != crc8(&data[..at]) {
+        return None;
+    }
+    Some((at + 1, number_len, block_size))
+}
+
+/// Reads the metadata of the FLAC stream from [file], leaving it at the first
+/// audio frame. Returns the STREAMINFO body.
+///
+fn flac_info(file: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut magic = [0; 4];
+    let mut info = None;
+
+    file.read_exact(&mut magic)?;
+    if &magic != b"fLaC" {
+        return Err(bad("not FLAC"));
+    }
+    loop {
+        let mut header = [0; 4];
+        file.read_exact(&mut header)
+            .map_err(|_| bad("cut metadata"))?;
+        let mut len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as u64;
+
+        if header[0] & 0x7f == 0 && len >= 34 {
+            let mut streaminfo = vec![0; 34];
+            file.read_exact(&mut streaminfo)?;
+            info = Some(streaminfo);
+            len -= 34;
+        }
+        if io::copy(&mut file.by_ref().take(len), &mut io::sink())? < len {
+            return Err(bad("cut metadata"));
+        }
+        if header[0] & 0x80 != 0 {
+            break;
+        }
+    }
+    info.ok_or_else(|| bad("no STREAMINFO"))
+}
+
+/// Reads the audio frames from [file], one at a time, and hands each one
+/// to [each]. A frame ends where the next valid header starts, its CRC-16 checked.
+///
+fn flac_frames(
+    file: &mut impl Read,
+    mut each: impl FnMut(&[u8]) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut data = vec![]; // The current frame, and a little of what follows.
+    let mut chunk = [0; 4096];
+    let mut pos = 0;
+    let mut crc = 0;
+
+    loop {
+        if data.len() < pos + 16 {
+            let n = file.read(&mut chunk)?;
+            if n > 0 {
+                data.extend_from_slice(&chunk[..n]);
+                continue;
+            }
+        }
+        if pos >= data.len() {
+            break;
+        }
+        if pos >= 6 && crc == 0 && data[pos] == 0xff && flac_frame_header(&data[pos..]).is_some() {
+            each(&data[..pos])?;
+            data.drain(..pos);
+            pos = 0;
+        }
+        crc = crc16_update(if pos == 0 { 0 } else { crc }, data[pos]);
+        pos += 1;
+    }
+    if !data.is_empty() {
+        each(&data)?;
+    }
+    Ok(())
+}
+
+/// Returns the sample rate, channels, and bits per sample of the stream
+/// of the STREAMINFO [info], packed.
+///
+fn flac_params(info: &[u8]) -> u64 {
+    u64::from_be_bytes(info[10..18].try_into().unwrap()) >> 36
+}
+
+/// Concatenates the audio frames of the [srcs] FLAC files into [dst], the same
+/// sample rate, channels, and bits per sample; the frames get renumbered
+/// as the ones of a variable block size stream, the metadata is left behind,
+/// save STREAMINFO. The sources are read one by one, frame after frame.
+///
+pub fn flac(srcs: &[&Path], dst: &Path) -> io::Result<()> {
+    let mut out = io::BufWriter::new(fs::File::create(dst)?);
+    let mut info: Option<Vec<u8>> = None;
+    let mut sample = 0;
+    let (mut block_min, mut block_max) = (u16::MAX, 0);
+    let (mut size_min, mut size_max) = (u32::MAX, 0);
+
+    out.write_all(b"fLaC\x80\x00\x00\x22")?;
+    out.write_all(&[0; 34])?; // STREAMINFO, to be filled in at the end.
+    for src in srcs {
+        let mut file = io::BufReader::new(fs::File::open(src)?);
+        let src_info = flac_info(&mut file)?;
+
+        match &info {
+            Some(info) if flac_params(info) != flac_params(&src_info) => {
+                return Err(bad("stream parameters differ"))
+            }
+            Some(_) => (),
+            None => info = Some(src_info),
+        }
+        flac_frames(&mut file, |frame| {
+            let (header_len, number_len, block_size) =
+                flac_frame_header(frame).ok_or_else(|| bad("bad frame"))?;
+            let block_size16 =
+                u16::try_from(block_size).map_err(|_| bad("block size beyond STREAMINFO"))?;
+            let mut renumbered = vec![0xff, 0xf9, frame[2], frame[3]];
+
+            renumbered.extend(flac_number(sample));
+            renumbered.extend(&frame[4 + number_len..header_len - 1]);
+            renumbered.push(crc8(&renumbered));
+            renumbered.extend(&frame[header_len..frame.len() - 2]);
+            renumbered.extend(crc16(&renumbered).to_be_bytes());
+            out.write_all(&renumbered)?;
+
+            block_min = block_min.min(block_size16);
+            block_max = block_max.max(block_size16);
+            size_min = size_min.min(renumbered.len() as u32);
+            size_max = size_max.max(renumbered.len() as u32);
+            sample += block_size;
+            Ok(())
+        })?;
+    }
+
+    let mut info = info.ok_or_else(|| bad("nothing to merge"))?;
+    if sample == 0 {
+        (block_min, size_min) = (0, 0);
+    }
+    info[0..2].copy_from_slice(&block_min.to_be_bytes());
+    info[2..4].copy_from_slice(&block_max.to_be_bytes());
+    info[4..7].copy_from_slice(&size_min.to_be_bytes()[1..]);
+    info[7..10].copy_from_slice(&size_max.to_be_bytes()[1..]);
+    let packed = u64::from_be_bytes(info[10..18].try_into().unwrap());
+    info[10..18].copy_from_slice(&(packed & !0xf_ffff_ffff | sample).to_be_bytes());
+    info[18..34].fill(0); // MD5 unknown.
+
+    let mut out = out.into_inner().map_err(|e| e.into_error())?;
+    out.seek(SeekFrom::Start(8))?;
+    out.write_all(&info)
+}
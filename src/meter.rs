@@ -0,0 +1,129 @@
+use crate::{human_duration, human_fine};
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+/// How often the progress is reported by a line, when the bar cannot be redrawn.
+const PLAIN_PERIOD: Duration = Duration::from_secs(5);
+const BAR_WIDTH: usize = 20;
+
+/// Copy progress: tracks and bytes done so far, out of the totals. Either
+/// redrawn in place as a bar, or printed as a plain line every few seconds.
+///
+pub struct Meter {
+    prefix: String,
+    tracks_total: u64,
+    bytes_total: u64,
+    tracks: u64,
+    bytes: u64,
+    start: Instant,
+    shown: Instant,
+    redraw: bool,
+}
+
+impl Meter {
+    /// Starts metering the copy of [tracks_total] tracks, [bytes_total] bytes.
+    /// Every line is prepended with [prefix].
+    ///
+    pub fn new(prefix: String, tracks_total: u64, bytes_total: u64, redraw: bool) -> Self {
+        let now = Instant::now();
+        let meter = Self {
+            prefix,
+            tracks_total,
+            bytes_total,
+            tracks: 0,
+            bytes: 0,
+            start: now,
+            shown: now,
+            redraw,
+        };
+
+        if redraw {
+            meter.draw();
+        }
+        meter
+    }
+
+    fn line(&self) -> String {
+        line(
+            self.tracks,
+            self.tracks_total,
+            self.bytes,
+            self.bytes_total,
+            self.start.elapsed().as_secs_f64(),
+        )
+    }
+
+    pub fn draw(&self) {
+        print!("\r\x1b[K{}{}", self.prefix, self.line());
+        io::stdout().flush().unwrap();
+    }
+
+    /// Erases the bar, so that something else can be printed on its line.
+    ///
+    pub fn clear(&self) {
+        if self.redraw {
+            print!("\r\x1b[K");
+        }
+    }
+
+    /// Counts one more track, [bytes] long, and shows the progress.
+    ///
+    pub fn advance(&mut self, bytes: u64, show: bool) {
+        self.tracks += 1;
+        self.bytes += bytes;
+        if self.redraw {
+            self.draw();
+        } else if show && self.shown.elapsed() >= PLAIN_PERIOD {
+            println!("{}{}", self.prefix, self.line());
+            self.shown = Instant::now();
+        }
+    }
+
+    /// Shows the final state, followed by [tail], unless [verbose];
+    /// otherwise just erases the bar.
+    ///
+    pub fn finish(&self, tail: &str, verbose: bool) {
+        self.clear();
+        if verbose {
+            io::stdout().flush().unwrap();
+        } else {
+            println!("{}{}{}", self.prefix, self.line(), tail);
+        }
+    }
+}
+
+/// Returns the progress line: the bar (bytes done), tracks and bytes
+/// against their totals, the throughput, and the estimated time remaining.
+///
+pub fn line(tracks: u64, tracks_total: u64, bytes: u64, bytes_total: u64, elapsed: f64) -> String {
+    let ratio = if bytes_total > 0 {
+        (bytes as f64 / bytes_total as f64).min(1.0)
+    } else {
+        1.0
+    };
+    let filled = (ratio * BAR_WIDTH as f64).round() as usize;
+    let rate = if elapsed > 0.0 {
+        bytes as f64 / elapsed
+    } else {
+        0.0
+    };
+    let eta = if rate > 0.0 {
+        human_duration((bytes_total.saturating_sub(bytes) as f64 / rate).round() as u64)
+    } else {
+        "-:--".to_string()
+    };
+
+    format!(
+        "[{}{}] {}/{}  {}/{}  {}/s  ETA {}",
+        "#".repeat(filled),
+        "-".repeat(BAR_WIDTH - filled),
+        tracks,
+        tracks_total,
+        human_fine(bytes),
+        human_fine(bytes_total),
+        human_fine(rate as u64),
+        eta
+    )
+}
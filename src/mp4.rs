@@ -0,0 +1,383 @@
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+    process,
+};
+
+/// A chapter of an MP4 audiobook; [start] and [end] are milliseconds.
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chapter {
+    pub start: u64,
+    pub end: u64,
+    pub title: String,
+}
+
+impl Chapter {
+    /// Returns true, if the chapter has no length, or ends before it starts.
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.end <= self.start
+    }
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn u64_at(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// Returns the atoms (type, body) inside [data], the body of a container atom.
+///
+fn atoms(data: &[u8]) -> Vec<(&[u8], &[u8])> {
+    let mut list = vec![];
+    let mut at = 0;
+
+    while at + 8 <= data.len() {
+        let (header, size) = match u32_at(data, at).unwrap() {
+            0 => (8, data.len() - at),
+            1 => match u64_at(data, at + 8) {
+                Some(size) => (16, size as usize),
+                None => break,
+            },
+            size => (8, size as usize),
+        };
+        if size < header || size > data.len() - at {
+            break;
+        }
+        list.push((&data[at + 4..at + 8], &data[at + header..at + size]));
+        at += size;
+    }
+    list
+}
+
+/// Returns the body of the atom down the [path] of types inside [data].
+///
+fn atom<'a>(data: &'a [u8], path: &[&[u8]]) -> Option<&'a [u8]> {
+    path.iter().try_fold(data, |body, kind| {
+        atoms(body)
+            .into_iter()
+            .find(|(k, _)| k == kind)
+            .map(|(_, b)| b)
+    })
+}
+
+/// Returns the body of the moov atom of the [file].
+///
+fn moov_read(file: &mut fs::File) -> io::Result<Vec<u8>> {
+    let len = file.metadata()?.len();
+    let mut at = 0;
+
+    while at + 8 <= len {
+        let mut header = [0_u8; 16];
+        file.seek(SeekFrom::Start(at))?;
+        file.read_exact(&mut header[..8])?;
+        let (skip, size) = match u32_at(&header, 0).unwrap() {
+            0 => (8, len - at),
+            1 => {
+                file.read_exact(&mut header[8..])?;
+                (16, u64_at(&header, 8).unwrap())
+            }
+            size => (8, size as u64),
+        };
+        if size < skip || size > len - at {
+            break;
+        }
+        if &header[4..8] == b"moov" {
+            let mut body = vec![0; (size - skip) as usize];
+            file.read_exact(&mut body)?;
+            return Ok(body);
+        }
+        at += size;
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "no moov atom"))
+}
+
+/// Returns the duration of the movie, milliseconds, by its mvhd atom.
+///
+fn duration_ms(moov: &[u8]) -> u64 {
+    let Some(mvhd) = atom(moov, &[b"mvhd"]) else {
+        return 0;
+    };
+    let (scale, duration) = if mvhd.first() == Some(&1) {
+        (u32_at(mvhd, 20), u64_at(mvhd, 24))
+    } else {
+        (u32_at(mvhd, 12), u32_at(mvhd, 16).map(|d| d as u64))
+    };
+
+    match (scale, duration) {
+        (Some(scale), Some(duration)) if scale > 0 => duration * 1000 / scale as u64,
+        _ => 0,
+    }
+}
+
+/// Returns the (start, title) of the Nero chapters in the chpl [body];
+/// start is in units of 100 ns.
+///
+fn chpl_parse(body: &[u8]) -> Vec<(u64, String)> {
+    let mut at = if body.first().copied().unwrap_or(0) > 0 {
+        8
+    } else {
+        4
+    };
+    let count = body.get(at).copied().unwrap_or(0);
+    let mut list = vec![];
+
+    at += 1;
+    for _ in 0..count {
+        let (Some(start), Some(len)) = (u64_at(body, at), body.get(at + 8)) else {
+            break;
+        };
+        let Some(title) = body.get(at + 9..at + 9 + *len as usize) else {
+            break;
+        };
+        list.push((start, String::from_utf8_lossy(title).to_string()));
+        at += 9 + *len as usize;
+    }
+    list
+}
+
+/// Returns the text of a QuickTime text track [sample].
+///
+fn text_sample(sample: &[u8]) -> String {
+    let len = u16::from_be_bytes([sample[0], sample[1]]) as usize;
+    let text = &sample[2..(2 + len).min(sample.len())];
+
+    if let Some(utf16) = text.strip_prefix(b"\xfe\xff") {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else {
+        String::from_utf8_lossy(text).to_string()
+    }
+}
+
+/// Returns the chapters of the QuickTime chapter track of the [moov], if any,
+/// reading the titles from the [file].
+///
+fn chapter_track(file: &mut fs::File, moov: &[u8]) -> Option<Vec<Chapter>> {
+    let traks: Vec<&[u8]> = atoms(moov)
+        .into_iter()
+        .filter(|(k, _)| *k == b"trak")
+        .map(|(_, b)| b)
+        .collect();
+    let chap_id = traks
+        .iter()
+        .find_map(|t| atom(t, &[b"tref", b"chap"]).and_then(|c| u32_at(c, 0)))?;
+    let trak = traks.iter().find(|t| {
+        atom(t, &[b"tkhd"]).and_then(|h| u32_at(h, if h.first() == Some(&1) { 20 } else { 12 }))
+            == Some(chap_id)
+    })?;
+    let mdhd = atom(trak, &[b"mdia", b"mdhd"])?;
+    let scale = u32_at(mdhd, if mdhd.first() == Some(&1) { 20 } else { 12 })? as u64;
+    let stbl = atom(trak, &[b"mdia", b"minf", b"stbl"])?;
+
+    let stts = atom(stbl, &[b"stts"])?;
+    let mut times = vec![]; // (start, duration) of every sample.
+    let mut t = 0;
+    for e in 0..u32_at(stts, 4)? as usize {
+        let (count, delta) = (u32_at(stts, 8 + e * 8)?, u32_at(stts, 12 + e * 8)? as u64);
+        for _ in 0..count {
+            times.push((t, delta));
+            t += delta;
+        }
+    }
+
+    let stsz = atom(stbl, &[b"stsz"])?;
+    let sizes: Vec<u64> = match u32_at(stsz, 4)? {
+        0 => (0..u32_at(stsz, 8)? as usize)
+            .map(|i| u32_at(stsz, 12 + i * 4).map(|s| s as u64))
+            .collect::<Option<_>>()?,
+        size => vec![size as u64; times.len()],
+    };
+    let offsets: Vec<u64> = if let Some(stco) = atom(stbl, &[b"stco"]) {
+        (0..u32_at(stco, 4)? as usize)
+            .map(|i| u32_at(stco, 8 + i * 4).map(|o| o as u64))
+            .collect::<Option<_>>()?
+    } else {
+        let co64 = atom(stbl, &[b"co64"])?;
+        (0..u32_at(co64, 4)? as usize)
+            .map(|i| u64_at(co64, 8 + i * 8))
+            .collect::<Option<_>>()?
+    };
+    let stsc = atom(stbl, &[b"stsc"])?;
+    let runs: Vec<(u32, u32)> = (0..u32_at(stsc, 4)? as usize)
+        .map(|i| Some((u32_at(stsc, 8 + i * 12)?, u32_at(stsc, 12 + i * 12)?)))
+        .collect::<Option<_>>()?;
+
+    let mut sample_offsets = vec![];
+    for (chunk, offset) in offsets.iter().enumerate() {
+        let per_chunk = runs
+            .iter()
+            .rev()
+            .find(|(first, _)| *first as usize <= chunk + 1)
+            .map_or(1, |(_, n)| *n);
+        let mut at = *offset;
+        for _ in 0..per_chunk {
+            let Some(size) = sizes.get(sample_offsets.len()) else {
+                break;
+            };
+            sample_offsets.push((at, *size));
+            at += size;
+        }
+    }
+
+    let mut chapters = vec![];
+    for ((start, duration), (offset, size)) in times.iter().zip(sample_offsets) {
+        let mut sample = vec![0; size.max(2) as usize];
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        file.read_exact(&mut sample).ok()?;
+        chapters.push(Chapter {
+            start: start * 1000 / scale.max(1),
+            end: (start + duration) * 1000 / scale.max(1),
+            title: text_sample(&sample),
+        });
+    }
+    Some(chapters).filter(|c| !c.is_empty())
+}
+
+/// Returns the chapters of the [path] MP4 file: from the QuickTime chapter
+/// track, if any, otherwise the Nero ones (chpl atom); none, if there are none.
+///
+pub fn chapters(path: &Path) -> io::Result<Vec<Chapter>> {
+    let mut file = fs::File::open(path)?;
+    let moov = moov_read(&mut file)?;
+
+    if let Some(chapters) = chapter_track(&mut file, &moov) {
+        return Ok(chapters);
+    }
+    let nero = atom(&moov, &[b"udta", b"chpl"]).map_or(vec![], chpl_parse);
+    let end = duration_ms(&moov);
+
+    Ok(nero
+        .iter()
+        .enumerate()
+        .map(|(i, (start, title))| Chapter {
+            start: start / 10_000,
+            end: nero.get(i + 1).map_or(end, |(next, _)| next / 10_000),
+            title: title.clone(),
+        })
+        .collect())
+}
+
+/// Returns the chapter title, made by the [template] from the chapter
+/// number [n] and its original [title]: "{n}" and "{title}" are replaced.
+///
+pub fn title_render(template: &str, n: usize, title: &str) -> String {
+    template
+        .replace("{n}", &n.to_string())
+        .replace("{title}", title)
+}
+
+fn ffmpeg(args: &[&std::ffi::OsStr]) -> io::Result<()> {
+    let status = process::Command::new("ffmpeg")
+        .args(["-v", "error", "-nostdin", "-y"])
+        .args(args)
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("ffmpeg failed: {}", status)))
+    }
+}
+
+fn seconds(ms: u64) -> String {
+    format!("{}.{:03}", ms / 1000, ms % 1000)
+}
+
+/// Cuts the [path] MP4 file into the [chapters], by remuxing, not re-encoding,
+/// with ffmpeg; writes the pieces, no chapters inside, to [dsts].
+/// The empty chapters are left out (see is_empty()).
+///
+pub fn split(path: &Path, chapters: &[Chapter], dsts: &[&Path]) -> io::Result<()> {
+    for (chapter, dst) in chapters.iter().zip(dsts).filter(|(c, _)| !c.is_empty()) {
+        let (start, duration) = (
+            seconds(chapter.start),
+            seconds(chapter.end.saturating_sub(chapter.start)),
+        );
+
+        ffmpeg(&[
+            "-ss".as_ref(),
+            start.as_ref(),
+            "-i".as_ref(),
+            path.as_os_str(),
+            "-t".as_ref(),
+            duration.as_ref(),
+            "-map".as_ref(),
+            "0:a:0".as_ref(),
+            "-c".as_ref(),
+            "copy".as_ref(),
+            "-map_chapters".as_ref(),
+            "-1".as_ref(),
+            dst.as_os_str(),
+        ])?;
+    }
+    Ok(())
+}
+
+/// Returns the [chapters] as an ffmpeg metadata file.
+///
+pub fn ffmetadata(chapters: &[Chapter]) -> String {
+    let escape = |s: &str| {
+        s.chars().fold(String::new(), |mut acc, c| {
+            if "=;#\\\n".contains(c) {
+                acc.push('\\');
+            }
+            acc.push(c);
+            acc
+        })
+    };
+
+    chapters
+        .iter()
+        .fold(";FFMETADATA1\n".to_string(), |acc, c| {
+            acc + &format!(
+                "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+                c.start,
+                c.end,
+                escape(&c.title)
+            )
+        })
+}
+
+/// Rewrites the chapter titles of the [path] MP4 file to [chapters] by
+/// remuxing it with ffmpeg, the audio and the tags copied as is; the old
+/// chapter track is left out, the new one is made of [chapters]. The file
+/// keeps its permissions.
+///
+pub fn retitle(path: &Path, chapters: &[Chapter]) -> io::Result<()> {
+    let dir = path.parent().unwrap();
+    let meta = tempfile::NamedTempFile::new()?;
+    let tmp = tempfile::Builder::new()
+        .suffix(&format!(
+            ".{}",
+            path.extension().unwrap_or_default().to_string_lossy()
+        ))
+        .tempfile_in(dir)?;
+
+    fs::write(meta.path(), ffmetadata(chapters))?;
+    ffmpeg(&[
+        "-i".as_ref(),
+        path.as_os_str(),
+        "-i".as_ref(),
+        meta.path().as_os_str(),
+        "-map".as_ref(),
+        "0:a".as_ref(),
+        "-map_metadata".as_ref(),
+        "0".as_ref(),
+        "-map_chapters".as_ref(),
+        "1".as_ref(),
+        "-c".as_ref(),
+        "copy".as_ref(),
+        tmp.path().as_os_str(),
+    ])?;
+    fs::set_permissions(tmp.path(), fs::metadata(path)?.permissions())?;
+    tmp.persist(path).map_err(|e| e.error)?;
+    Ok(())
+}
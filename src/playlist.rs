@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+
+/// A track of the destination album, as listed in a playlist.
+///
+pub struct Entry {
+    pub path: PathBuf, // Relative to the playlist.
+    pub seconds: Option<u32>,
+    pub title: String,
+}
+
+/// The supported playlist formats, by file extension.
+///
+pub const FORMATS: [&str; 3] = ["m3u8", "pls", "xspf"];
+
+fn path_str(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Returns the extended M3U playlist (UTF-8) of the [entries].
+///
+pub fn m3u8(entries: &[Entry]) -> String {
+    let mut text = "#EXTM3U\n".to_string();
+
+    for e in entries {
+        text += &format!(
+            "#EXTINF:{},{}\n{}\n",
+            e.seconds.map_or(-1, |s| s as i64),
+            e.title.replace('\n', " "),
+            path_str(&e.path)
+        );
+    }
+    text
+}
+
+/// Returns the PLS playlist of the [entries].
+///
+pub fn pls(entries: &[Entry]) -> String {
+    let mut text = "[playlist]\n".to_string();
+
+    for (i, e) in entries.iter().enumerate() {
+        text += &format!(
+            "File{0}={1}\nTitle{0}={2}\nLength{0}={3}\n",
+            i + 1,
+            path_str(&e.path),
+            e.title.replace('\n', " "),
+            e.seconds.map_or(-1, |s| s as i64)
+        );
+    }
+    text + &format!("NumberOfEntries={}\nVersion=2\n", entries.len())
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Returns the relative URI of the [path].
+///
+fn uri_encode(path: &Path) -> String {
+    path_str(path)
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Returns the XSPF playlist of the [entries].
+///
+pub fn xspf(entries: &[Entry]) -> String {
+    let mut text = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                    <playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n  <trackList>\n"
+        .to_string();
+
+    for e in entries {
+        text += &format!(
+            "    <track>\n      <location>{}</location>\n      <title>{}</title>\n",
+            uri_encode(&e.path),
+            xml_escape(&e.title)
+        );
+        if let Some(seconds) = e.seconds {
+            text += &format!("      <duration>{}</duration>\n", seconds as u64 * 1000);
+        }
+        text += "    </track>\n";
+    }
+    text + "  </trackList>\n</playlist>\n"
+}
+
+/// Returns the playlist of the [entries] in the [format] (see FORMATS).
+///
+pub fn render(format: &str, entries: &[Entry]) -> String {
+    match format {
+        "pls" => pls(entries),
+        "xspf" => xspf(entries),
+        _ => m3u8(entries),
+    }
+}
@@ -0,0 +1,720 @@
+use crate::{
+    human_duration, human_fine, meter::Meter, mp4::Chapter, paint, str_shrink, AudioProps,
+    BDELIM_ICON, COLUMN_ICON, DONE_ICON, ICONS_FANCY, START_ICON, STOP_ICON, WARNING_ICON,
+};
+use spinner as daddy;
+use std::{path::Path, time::Duration};
+
+static MOON: [&str; 8] = [" 🌑", " 🌒", " 🌓", " 🌔", " 🌕", " 🌖", " 🌗", " 🌘"];
+static STICK: [&str; 4] = [" |", " /", " -", " \\"];
+
+/// Everything worth reporting about a run, from the first pass through
+/// the source to the final summary.
+///
+pub trait Progress {
+    /// Counting starts.
+    fn scan_start(&mut self);
+    /// An audio file found while counting.
+    fn scan_entry(&mut self, path: &Path);
+    /// A file, which looks like audio, but isn't; [bytes] long; [reason] tells why.
+    fn suspicious(&mut self, path: &Path, bytes: u64, reason: &str);
+    /// Counting done: [tracks] valid, [bytes] in total.
+    fn scan_done(&mut self, tracks: u64, bytes: u64);
+    /// Copying starts: [tracks] to be copied, [bytes] in total.
+    fn copy_start(&mut self, tracks: u64, bytes: u64);
+    /// The [ii] track is being copied from [src] to [dst].
+    fn track_started(&mut self, ii: u64, src: &Path, dst: &Path);
+    /// The [ii] track (out of [tracks_total]) is at [dst]; its size went
+    /// from [src_bytes] to [dst_bytes] (0, if nothing was written);
+    /// [audio] is its length and such, if known.
+    fn track_finished(
+        &mut self,
+        ii: u64,
+        tracks_total: u64,
+        dst: &Path,
+        src_bytes: u64,
+        dst_bytes: u64,
+        audio: Option<AudioProps>,
+    );
+    /// The [chapters] of the [path] audiobook.
+    fn chapters(&mut self, path: &Path, chapters: &[Chapter]);
+    /// Something the user should know; [text] is a complete report line.
+    fn warning(&mut self, text: &str);
+    /// Something that stops the run; [text] is a complete report line.
+    fn error(&mut self, text: &str);
+    /// Count only (no copy) done: [tracks], [bytes], [duration] of audio
+    /// (seconds), in [seconds].
+    fn counted(&mut self, tracks: u64, bytes: u64, duration: u64, seconds: f64);
+    /// Album check done: [tracks], [problems] found, in [seconds].
+    fn checked(&mut self, tracks: u64, problems: u64, seconds: f64);
+    /// Undo done: [changes] undone, [complaints] about the rest.
+    fn undone(&mut self, changes: u64, complaints: u64);
+    /// Batch done: the [albums] by name, each with its failure, if any,
+    /// and its [seconds].
+    fn batched(&mut self, albums: &[(String, Option<String>, u64)]);
+    /// All done: [tracks], [bytes], in [seconds]; the copy [throughput],
+    /// bytes per second, if anything was written.
+    fn done(&mut self, tracks: u64, bytes: u64, seconds: f64, throughput: Option<u64>);
+}
+
+/// Returns the verbose report line of the [ii] track (see Progress::track_finished()).
+///
+fn track_line(
+    ii: u64,
+    tracks_total: u64,
+    dst: &Path,
+    src_bytes: u64,
+    dst_bytes: u64,
+    audio: Option<AudioProps>,
+) -> String {
+    let width = format!("{}", tracks_total).len();
+    let mut line = format!(
+        "{:1$}/{2} {3} {4}",
+        ii,
+        width,
+        tracks_total,
+        *COLUMN_ICON,
+        dst.display(),
+    );
+
+    if let Some(audio) = audio {
+        line += &format!(
+            "  {} {}, {}kbps",
+            *COLUMN_ICON,
+            human_duration(audio.seconds as u64),
+            audio.bitrate
+        );
+    }
+    if dst_bytes != src_bytes {
+        if dst_bytes == 0 {
+            line += &format!("  {} {}", *COLUMN_ICON, human_fine(src_bytes));
+        } else {
+            line += &format!(
+                "  {} {:+}",
+                *COLUMN_ICON,
+                dst_bytes as i64 - src_bytes as i64
+            );
+        }
+    }
+    line
+}
+
+/// Returns the report lines of the [chapters] of the [path] audiobook.
+///
+fn chapter_lines(path: &Path, chapters: &[Chapter]) -> Vec<String> {
+    let width = format!("{}", chapters.len()).len();
+    let mut lines = vec![format!(
+        " {} {}  {} {} chapter(s)",
+        *BDELIM_ICON,
+        path.display(),
+        *COLUMN_ICON,
+        chapters.len()
+    )];
+
+    for (i, c) in chapters.iter().enumerate() {
+        lines.push(format!(
+            "   {:1$}  {2:>8}  {3}",
+            i + 1,
+            width,
+            human_duration(c.start / 1000),
+            c.title
+        ));
+    }
+    lines
+}
+
+const GREEN: u8 = 32;
+const YELLOW: u8 = 33;
+const RED: u8 = 31;
+
+fn done_lines(tracks: u64, bytes: u64, seconds: f64, throughput: Option<u64>) -> String {
+    let mut lines = paint(
+        &format!(
+            " {} Done ({}, {}; {:.1}s).",
+            *DONE_ICON,
+            tracks,
+            human_fine(bytes),
+            seconds
+        ),
+        GREEN,
+    );
+
+    if let Some(rate) = throughput {
+        lines += &format!("\n {} Throughput: {}/s.", *DONE_ICON, human_fine(rate));
+    }
+    lines
+}
+
+fn undone_line(changes: u64, complaints: u64) -> String {
+    if complaints > 0 {
+        format!(
+            " {} Undone: {} change(s); see the complaints above.",
+            *WARNING_ICON, changes
+        )
+    } else {
+        format!(" {} Undone: {} change(s).", *DONE_ICON, changes)
+    }
+}
+
+fn checked_line(tracks: u64, problems: u64, seconds: f64) -> String {
+    format!(
+        " {} Checked: {} file(s); {}; Time: {:.1}s",
+        if problems > 0 {
+            *WARNING_ICON
+        } else {
+            *DONE_ICON
+        },
+        tracks,
+        match problems {
+            0 => "no problems found".to_string(),
+            n => format!("{} problem(s)", n),
+        },
+        seconds
+    )
+}
+
+fn batched_lines(albums: &[(String, Option<String>, u64)]) -> String {
+    let width = albums
+        .iter()
+        .map(|(name, _, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    let failed = albums.iter().filter(|(_, f, _)| f.is_some()).count();
+    let mut lines = String::new();
+
+    for (name, failure, seconds) in albums {
+        lines += &format!(
+            "\n {} {:<w$}  {} {:>8}  {}",
+            if failure.is_none() {
+                *DONE_ICON
+            } else {
+                *WARNING_ICON
+            },
+            name,
+            *COLUMN_ICON,
+            human_duration(*seconds),
+            failure.as_deref().unwrap_or("done"),
+            w = width
+        );
+    }
+    lines
+        + &format!(
+            "\n {} Batch: {} album(s) done, {} failed",
+            if failed == 0 {
+                *DONE_ICON
+            } else {
+                *WARNING_ICON
+            },
+            albums.len() - failed,
+            failed
+        )
+}
+
+fn counted_line(tracks: u64, bytes: u64, duration: u64, seconds: f64) -> String {
+    let mut line = format!(
+        " {} Valid: {} file(s); Volume: {}",
+        if tracks > 0 {
+            *DONE_ICON
+        } else {
+            *WARNING_ICON
+        },
+        tracks,
+        human_fine(bytes)
+    );
+
+    if tracks > 1 {
+        line += &format!("; Average: {}", human_fine(bytes / tracks));
+    }
+    if duration > 0 {
+        line += &format!("; Duration: {}", human_duration(duration));
+        if tracks > 1 {
+            line += &format!(" (average {})", human_duration(duration / tracks));
+        }
+    }
+    line + &format!("; Time: {:.1}s", seconds)
+}
+
+/// Progress for an interactive terminal: a spinner while counting,
+/// the bar, redrawn in place, while copying.
+///
+pub struct TerminalProgress {
+    spinner: Option<daddy::SpinnerHandle>,
+    meter: Option<Meter>,
+    verbose: bool,
+}
+
+impl TerminalProgress {
+    pub fn new(verbose: bool) -> Self {
+        Self {
+            spinner: None,
+            meter: None,
+            verbose,
+        }
+    }
+
+    /// Stops the spinner, if it is still there, leaving its line behind.
+    ///
+    fn spinner_stop(&mut self) {
+        if let Some(spinner) = self.spinner.take() {
+            spinner.close();
+            println!();
+        }
+    }
+
+    fn say(&mut self, text: &str) {
+        self.spinner_stop();
+        if let Some(meter) = &self.meter {
+            meter.clear();
+        }
+        println!("{}", text);
+        if let Some(meter) = &self.meter {
+            meter.draw();
+        }
+    }
+}
+
+impl Progress for TerminalProgress {
+    fn scan_start(&mut self) {
+        let frames = if *ICONS_FANCY { &MOON[..] } else { &STICK[..] };
+
+        self.spinner = Some(
+            daddy::SpinnerBuilder::new("".into())
+                .spinner(frames.to_vec())
+                .step(Duration::from_millis(80))
+                .start(),
+        );
+    }
+
+    fn scan_entry(&mut self, path: &Path) {
+        if let Some(spinner) = &self.spinner {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            spinner.update(str_shrink(&name, 72) + *BDELIM_ICON);
+        }
+    }
+
+    fn suspicious(&mut self, _path: &Path, _bytes: u64, _reason: &str) {}
+
+    fn scan_done(&mut self, _tracks: u64, _bytes: u64) {
+        self.spinner_stop();
+    }
+
+    fn copy_start(&mut self, tracks: u64, bytes: u64) {
+        let prefix = if self.verbose {
+            "".to_string()
+        } else {
+            format!(" {} ", *START_ICON)
+        };
+        self.meter = Some(Meter::new(prefix, tracks, bytes, true));
+    }
+
+    fn track_started(&mut self, _ii: u64, _src: &Path, _dst: &Path) {}
+
+    fn track_finished(
+        &mut self,
+        ii: u64,
+        tracks_total: u64,
+        dst: &Path,
+        src_bytes: u64,
+        dst_bytes: u64,
+        audio: Option<AudioProps>,
+    ) {
+        if self.verbose {
+            self.say(&track_line(
+                ii,
+                tracks_total,
+                dst,
+                src_bytes,
+                dst_bytes,
+                audio,
+            ));
+        }
+        if let Some(meter) = self.meter.as_mut() {
+            meter.advance(src_bytes, true);
+        }
+    }
+
+    fn chapters(&mut self, path: &Path, chapters: &[Chapter]) {
+        self.say(&chapter_lines(path, chapters).join("\n"));
+    }
+
+    fn warning(&mut self, text: &str) {
+        self.say(&paint(text, YELLOW));
+    }
+
+    fn error(&mut self, text: &str) {
+        self.say(&paint(text, RED));
+    }
+
+    fn counted(&mut self, tracks: u64, bytes: u64, duration: u64, seconds: f64) {
+        println!("{}", counted_line(tracks, bytes, duration, seconds));
+    }
+
+    fn checked(&mut self, tracks: u64, problems: u64, seconds: f64) {
+        println!("{}", checked_line(tracks, problems, seconds));
+    }
+
+    fn undone(&mut self, changes: u64, complaints: u64) {
+        self.say(&undone_line(changes, complaints));
+    }
+
+    fn batched(&mut self, albums: &[(String, Option<String>, u64)]) {
+        self.say(&batched_lines(albums));
+    }
+
+    fn done(&mut self, tracks: u64, bytes: u64, seconds: f64, throughput: Option<u64>) {
+        if let Some(meter) = self.meter.take() {
+            meter.finish(&format!(" {}", *STOP_ICON), self.verbose);
+        }
+        println!("{}", done_lines(tracks, bytes, seconds, throughput));
+    }
+}
+
+/// Progress for a log file, or a pipe: no animation, no control sequences;
+/// a line per track, if verbose, otherwise a line every few seconds.
+///
+pub struct PlainProgress {
+    meter: Option<Meter>,
+    verbose: bool,
+}
+
+impl PlainProgress {
+    pub fn new(verbose: bool) -> Self {
+        Self {
+            meter: None,
+            verbose,
+        }
+    }
+}
+
+impl Progress for PlainProgress {
+    fn scan_start(&mut self) {}
+
+    fn scan_entry(&mut self, _path: &Path) {}
+
+    fn suspicious(&mut self, _path: &Path, _bytes: u64, _reason: &str) {}
+
+    fn scan_done(&mut self, _tracks: u64, _bytes: u64) {}
+
+    fn copy_start(&mut self, tracks: u64, bytes: u64) {
+        self.meter = Some(Meter::new(
+            format!(" {} ", *START_ICON),
+            tracks,
+            bytes,
+            false,
+        ));
+    }
+
+    fn track_started(&mut self, _ii: u64, _src: &Path, _dst: &Path) {}
+
+    fn track_finished(
+        &mut self,
+        ii: u64,
+        tracks_total: u64,
+        dst: &Path,
+        src_bytes: u64,
+        dst_bytes: u64,
+        audio: Option<AudioProps>,
+    ) {
+        if self.verbose {
+            println!(
+                "{}",
+                track_line(ii, tracks_total, dst, src_bytes, dst_bytes, audio)
+            );
+        }
+        if let Some(meter) = self.meter.as_mut() {
+            meter.advance(src_bytes, !self.verbose);
+        }
+    }
+
+    fn chapters(&mut self, path: &Path, chapters: &[Chapter]) {
+        println!("{}", chapter_lines(path, chapters).join("\n"));
+    }
+
+    fn warning(&mut self, text: &str) {
+        println!("{}", paint(text, YELLOW));
+    }
+
+    fn error(&mut self, text: &str) {
+        println!("{}", paint(text, RED));
+    }
+
+    fn counted(&mut self, tracks: u64, bytes: u64, duration: u64, seconds: f64) {
+        println!("{}", counted_line(tracks, bytes, duration, seconds));
+    }
+
+    fn checked(&mut self, tracks: u64, problems: u64, seconds: f64) {
+        println!("{}", checked_line(tracks, problems, seconds));
+    }
+
+    fn undone(&mut self, changes: u64, complaints: u64) {
+        println!("{}", undone_line(changes, complaints));
+    }
+
+    fn batched(&mut self, albums: &[(String, Option<String>, u64)]) {
+        println!("{}", batched_lines(albums));
+    }
+
+    fn done(&mut self, tracks: u64, bytes: u64, seconds: f64, throughput: Option<u64>) {
+        if let Some(meter) = self.meter.take() {
+            meter.finish(&format!(" {}", *STOP_ICON), self.verbose);
+        }
+        println!("{}", done_lines(tracks, bytes, seconds, throughput));
+    }
+}
+
+/// Returns [s] as a JSON string literal.
+///
+pub fn json_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Progress for other programs: every event is a JSON object on a line of its own.
+///
+pub struct JsonProgress {}
+
+impl JsonProgress {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    fn emit(&self, event: &str, fields: &[(&str, String)]) {
+        let mut line = format!("{{\"event\":{}", json_str(event));
+
+        for (key, value) in fields {
+            line += &format!(",{}:{}", json_str(key), value);
+        }
+        println!("{}}}", line);
+    }
+}
+
+fn json_path(path: &Path) -> String {
+    json_str(&path.to_string_lossy())
+}
+
+impl Progress for JsonProgress {
+    fn scan_start(&mut self) {
+        self.emit("scan_start", &[]);
+    }
+
+    fn scan_entry(&mut self, path: &Path) {
+        self.emit("scan_entry", &[("path", json_path(path))]);
+    }
+
+    fn suspicious(&mut self, path: &Path, bytes: u64, reason: &str) {
+        self.emit(
+            "suspicious",
+            &[
+                ("path", json_path(path)),
+                ("bytes", bytes.to_string()),
+                ("reason", json_str(reason)),
+            ],
+        );
+    }
+
+    fn scan_done(&mut self, tracks: u64, bytes: u64) {
+        self.emit(
+            "scan_done",
+            &[("tracks", tracks.to_string()), ("bytes", bytes.to_string())],
+        );
+    }
+
+    fn copy_start(&mut self, tracks: u64, bytes: u64) {
+        self.emit(
+            "copy_start",
+            &[("tracks", tracks.to_string()), ("bytes", bytes.to_string())],
+        );
+    }
+
+    fn track_started(&mut self, ii: u64, src: &Path, dst: &Path) {
+        self.emit(
+            "track_started",
+            &[
+                ("track", ii.to_string()),
+                ("src", json_path(src)),
+                ("dst", json_path(dst)),
+            ],
+        );
+    }
+
+    fn track_finished(
+        &mut self,
+        ii: u64,
+        tracks_total: u64,
+        dst: &Path,
+        src_bytes: u64,
+        dst_bytes: u64,
+        audio: Option<AudioProps>,
+    ) {
+        let null = "null".to_string();
+
+        self.emit(
+            "track_finished",
+            &[
+                ("track", ii.to_string()),
+                ("tracks_total", tracks_total.to_string()),
+                ("dst", json_path(dst)),
+                ("src_bytes", src_bytes.to_string()),
+                ("dst_bytes", dst_bytes.to_string()),
+                (
+                    "seconds",
+                    audio.map_or(null.clone(), |a| a.seconds.to_string()),
+                ),
+                ("bitrate", audio.map_or(null, |a| a.bitrate.to_string())),
+            ],
+        );
+    }
+
+    fn chapters(&mut self, path: &Path, chapters: &[Chapter]) {
+        let list: Vec<String> = chapters
+            .iter()
+            .map(|c| {
+                format!(
+                    "{{\"start\":{},\"end\":{},\"title\":{}}}",
+                    c.start,
+                    c.end,
+                    json_str(&c.title)
+                )
+            })
+            .collect();
+
+        self.emit(
+            "chapters",
+            &[
+                ("path", json_path(path)),
+                ("chapters", format!("[{}]", list.join(","))),
+            ],
+        );
+    }
+
+    fn warning(&mut self, text: &str) {
+        self.emit("warning", &[("text", json_str(text.trim()))]);
+    }
+
+    fn error(&mut self, text: &str) {
+        self.emit("error", &[("text", json_str(text.trim()))]);
+    }
+
+    fn counted(&mut self, tracks: u64, bytes: u64, duration: u64, seconds: f64) {
+        self.emit(
+            "counted",
+            &[
+                ("tracks", tracks.to_string()),
+                ("bytes", bytes.to_string()),
+                ("duration", duration.to_string()),
+                ("seconds", format!("{:.3}", seconds)),
+            ],
+        );
+    }
+
+    fn checked(&mut self, tracks: u64, problems: u64, seconds: f64) {
+        self.emit(
+            "checked",
+            &[
+                ("tracks", tracks.to_string()),
+                ("problems", problems.to_string()),
+                ("seconds", format!("{:.3}", seconds)),
+            ],
+        );
+    }
+
+    fn undone(&mut self, changes: u64, complaints: u64) {
+        self.emit(
+            "undone",
+            &[
+                ("changes", changes.to_string()),
+                ("complaints", complaints.to_string()),
+            ],
+        );
+    }
+
+    fn batched(&mut self, albums: &[(String, Option<String>, u64)]) {
+        let list: Vec<String> = albums
+            .iter()
+            .map(|(name, failure, seconds)| {
+                format!(
+                    "{{\"album\":{},\"failure\":{},\"seconds\":{}}}",
+                    json_str(name),
+                    failure.as_deref().map_or("null".to_string(), json_str),
+                    seconds
+                )
+            })
+            .collect();
+
+        self.emit("batched", &[("albums", format!("[{}]", list.join(",")))]);
+    }
+
+    fn done(&mut self, tracks: u64, bytes: u64, seconds: f64, throughput: Option<u64>) {
+        self.emit(
+            "done",
+            &[
+                ("tracks", tracks.to_string()),
+                ("bytes", bytes.to_string()),
+                ("seconds", format!("{:.3}", seconds)),
+                (
+                    "throughput",
+                    throughput.map_or("null".to_string(), |t| t.to_string()),
+                ),
+            ],
+        );
+    }
+}
+
+/// No progress at all; errors only, to stderr.
+///
+pub struct SilentProgress {}
+
+impl SilentProgress {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+impl Progress for SilentProgress {
+    fn scan_start(&mut self) {}
+    fn scan_entry(&mut self, _path: &Path) {}
+    fn suspicious(&mut self, _path: &Path, _bytes: u64, _reason: &str) {}
+    fn scan_done(&mut self, _tracks: u64, _bytes: u64) {}
+    fn copy_start(&mut self, _tracks: u64, _bytes: u64) {}
+    fn track_started(&mut self, _ii: u64, _src: &Path, _dst: &Path) {}
+    fn track_finished(
+        &mut self,
+        _ii: u64,
+        _tracks_total: u64,
+        _dst: &Path,
+        _src_bytes: u64,
+        _dst_bytes: u64,
+        _audio: Option<AudioProps>,
+    ) {
+    }
+    fn chapters(&mut self, _path: &Path, _chapters: &[Chapter]) {}
+    fn warning(&mut self, _text: &str) {}
+    fn error(&mut self, text: &str) {
+        eprintln!("{}", text);
+    }
+    fn counted(&mut self, _tracks: u64, _bytes: u64, _duration: u64, _seconds: f64) {}
+    fn checked(&mut self, _tracks: u64, _problems: u64, _seconds: f64) {}
+    fn undone(&mut self, _changes: u64, _complaints: u64) {}
+    fn batched(&mut self, albums: &[(String, Option<String>, u64)]) {
+        for (name, failure, _) in albums {
+            if let Some(failure) = failure {
+                eprintln!("Batch album failed: {}: {}", name, failure);
+            }
+        }
+    }
+    fn done(&mut self, _tracks: u64, _bytes: u64, _seconds: f64, _throughput: Option<u64>) {}
+}
@@ -0,0 +1,214 @@
+use crate::id3;
+use std::{
+    fmt, fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// An audio container (or codec), as told by the magic bytes of a file.
+///
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Container {
+    Mpeg,
+    Aac, // ADTS stream.
+    Ogg(OggCodec),
+    Mp4,
+    Asf,
+    Flac,
+    Ape,
+    Wav,
+    Unknown,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OggCodec {
+    Vorbis,
+    Opus,
+    Flac,
+    Speex,
+    Other,
+}
+
+impl Container {
+    /// Returns the file extensions (upper case), proper for the container.
+    ///
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            Container::Mpeg => &["MP3"],
+            Container::Aac => &["AAC"],
+            Container::Ogg(OggCodec::Opus) => &["OPUS", "OGG"],
+            Container::Ogg(_) => &["OGG", "OPUS"],
+            Container::Mp4 => &["M4A", "M4B"],
+            Container::Asf => &["WMA"],
+            Container::Flac => &["FLAC"],
+            Container::Ape => &["APE"],
+            Container::Wav => &["WAV"],
+            Container::Unknown => &[],
+        }
+    }
+}
+
+impl fmt::Display for Container {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Container::Mpeg => "MPEG audio",
+            Container::Aac => "raw AAC",
+            Container::Ogg(OggCodec::Vorbis) => "Ogg Vorbis",
+            Container::Ogg(OggCodec::Opus) => "Ogg Opus",
+            Container::Ogg(OggCodec::Flac) => "Ogg FLAC",
+            Container::Ogg(OggCodec::Speex) => "Ogg Speex",
+            Container::Ogg(OggCodec::Other) => "Ogg (not audio)",
+            Container::Mp4 => "MP4",
+            Container::Asf => "ASF",
+            Container::Flac => "FLAC",
+            Container::Ape => "Monkey's Audio",
+            Container::Wav => "RIFF WAVE",
+            Container::Unknown => "unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+const ASF_GUID: [u8; 8] = [0x30, 0x26, 0xb2, 0x75, 0x8e, 0x66, 0xcf, 0x11];
+
+/// Tells the container by the [head] bytes of a file (after the ID3v2 tag, if any).
+///
+pub fn container_of(head: &[u8]) -> Container {
+    let starts = |magic: &[u8]| head.starts_with(magic);
+
+    if starts(b"fLaC") {
+        Container::Flac
+    } else if starts(b"OggS") {
+        let packet = head.get(28..).unwrap_or_default();
+        Container::Ogg(if packet.starts_with(b"\x01vorbis") {
+            OggCodec::Vorbis
+        } else if packet.starts_with(b"OpusHead") {
+            OggCodec::Opus
+        } else if packet.starts_with(b"\x7fFLAC") {
+            OggCodec::Flac
+        } else if packet.starts_with(b"Speex   ") {
+            OggCodec::Speex
+        } else {
+            OggCodec::Other
+        })
+    } else if head.len() >= 8 && &head[4..8] == b"ftyp" {
+        Container::Mp4
+    } else if starts(&ASF_GUID) {
+        Container::Asf
+    } else if starts(b"MAC ") {
+        Container::Ape
+    } else if starts(b"RIFF") && head.len() >= 12 && &head[8..12] == b"WAVE" {
+        Container::Wav
+    } else if head.len() >= 2 && head[0] == 0xff && head[1] & 0xe0 == 0xe0 {
+        if head[1] & 0x06 == 0 {
+            Container::Aac // MPEG sync, layer bits 00.
+        } else {
+            Container::Mpeg
+        }
+    } else {
+        Container::Unknown
+    }
+}
+
+/// The bytes at the start of [file], past the ID3v2 tag, if any; and
+/// true, if the tag claims more bytes than there are.
+///
+fn head_read(file: &mut fs::File, len: u64) -> io::Result<(Vec<u8>, bool)> {
+    let mut head = vec![0; 64.min(len as usize)];
+    file.read_exact(&mut head)?;
+
+    let skip = id3::head_size(&head);
+    if skip == 0 {
+        return Ok((head, false));
+    }
+    if skip >= len {
+        return Ok((vec![], true));
+    }
+    file.seek(SeekFrom::Start(skip))?;
+    let mut head = vec![0; 64.min((len - skip) as usize)];
+    file.read_exact(&mut head)?;
+    Ok((head, false))
+}
+
+/// Returns the container of the [path] file by its content.
+///
+pub fn sniff(path: &Path) -> io::Result<Container> {
+    let mut file = fs::File::open(path)?;
+    let len = file.metadata()?.len();
+
+    Ok(container_of(&head_read(&mut file, len)?.0))
+}
+
+/// Returns the proper extension (lower case) for the [path] file, if its
+/// content is known, and does not fit its extension; None otherwise.
+///
+pub fn ext_mismatch(path: &Path) -> Option<(Container, String)> {
+    let ext = path.extension()?.to_string_lossy().to_string();
+    let container = sniff(path).ok()?;
+
+    if container == Container::Unknown || is_ext_fit(container, &ext) {
+        None
+    } else {
+        Some((container, container.extensions()[0].to_lowercase()))
+    }
+}
+
+/// Returns true, if the container is fit for a file with the [ext] extension.
+///
+pub fn is_ext_fit(container: Container, ext: &str) -> bool {
+    container
+        .extensions()
+        .iter()
+        .any(|e| e.eq_ignore_ascii_case(ext))
+}
+
+/// Returns the minimal header size for the [container], to be readable.
+///
+fn header_min(container: Container) -> u64 {
+    match container {
+        Container::Flac => 4 + 4 + 34, // Magic, block header, STREAMINFO.
+        Container::Ogg(_) => 28 + 19,
+        Container::Mp4 => 16,
+        Container::Asf => 30,
+        Container::Ape => 32,
+        Container::Wav => 44,
+        _ => 4,
+    }
+}
+
+/// Tells, why the [path] file with an audio extension cannot be read,
+/// as far as its content can show.
+///
+pub fn diagnose(path: &Path) -> String {
+    let ext = path
+        .extension()
+        .map_or("".to_string(), |e| e.to_string_lossy().to_string());
+    let mut file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            return "unreadable: permission denied".to_string()
+        }
+        Err(e) => return format!("unreadable: {}", e),
+    };
+    let len = file.metadata().map_or(0, |m| m.len());
+
+    if len == 0 {
+        return "zero length".to_string();
+    }
+    let (head, tag_cut) = match head_read(&mut file, len) {
+        Ok(read) => read,
+        Err(e) => return format!("unreadable: {}", e),
+    };
+    if tag_cut {
+        return "truncated: nothing but a (cut) ID3v2 tag".to_string();
+    }
+
+    match container_of(&head) {
+        Container::Unknown if head.iter().all(|b| *b == 0) => "nothing but zeros".to_string(),
+        Container::Unknown => format!("not audio: unknown content for .{}", ext),
+        c if !is_ext_fit(c, &ext) => format!("wrong container: {} named .{}", c, ext),
+        c if (head.len() as u64) < header_min(c) => format!("truncated header: {}", c),
+        c @ Container::Ogg(OggCodec::Other) => format!("unsupported codec: {}", c),
+        c => format!("{}, damaged beyond reading", c),
+    }
+}
@@ -0,0 +1,69 @@
+use std::{ffi::CString, os::raw::c_char, path::Path};
+use taglib_sys as ll;
+
+// The property interface of the TagLib C bindings (TagLib 1.13+)
+// is not covered by the taglib crate.
+extern "C" {
+    fn taglib_property_set(file: *mut ll::TagLib_File, prop: *const c_char, value: *const c_char);
+}
+
+/// An audio file opened for setting arbitrary tag properties,
+/// e.g. DISCNUMBER, which the plain Tag interface knows nothing about.
+///
+pub struct PropFile {
+    raw: *mut ll::TagLib_File,
+}
+
+impl PropFile {
+    /// Opens [path] for property editing; None, if TagLib fails to make sense of it.
+    ///
+    pub fn open(path: &Path) -> Option<Self> {
+        let name = CString::new(path.to_str()?).ok()?;
+        let raw = unsafe { ll::taglib_file_new(name.as_ptr()) };
+
+        if raw.is_null() {
+            return None;
+        }
+        if unsafe { ll::taglib_file_is_valid(raw) } == 0 {
+            unsafe { ll::taglib_file_free(raw) };
+            return None;
+        }
+        Some(Self { raw })
+    }
+
+    /// Sets [prop] to [value]; an empty [value] removes the property.
+    ///
+    pub fn set(&mut self, prop: &str, value: &str) {
+        let prop = CString::new(prop).unwrap();
+        let value = CString::new(value).unwrap();
+
+        unsafe { taglib_property_set(self.raw, prop.as_ptr(), value.as_ptr()) };
+    }
+
+    /// Writes the changes to disk.
+    ///
+    pub fn save(&self) -> bool {
+        unsafe { ll::taglib_file_save(self.raw) != 0 }
+    }
+}
+
+impl Drop for PropFile {
+    fn drop(&mut self) {
+        unsafe { ll::taglib_file_free(self.raw) };
+    }
+}
+
+/// Sets all the [props] of [path] audio file at once.
+/// Returns false, if the file could not be opened or saved.
+///
+pub fn properties_set(path: &Path, props: &[(&str, String)]) -> bool {
+    match PropFile::open(path) {
+        Some(mut file) => {
+            for (prop, value) in props {
+                file.set(prop, value);
+            }
+            file.save()
+        }
+        None => false,
+    }
+}
@@ -124,6 +124,843 @@ fn test_human_fine() {
     assert_eq!(human_fine(1024.0_f64.powi(4) as u64), "1.00TB");
 }
 
+#[test]
+fn test_human_duration() {
+    assert_eq!(human_duration(0), "0:00");
+    assert_eq!(human_duration(59), "0:59");
+    assert_eq!(human_duration(201), "3:21");
+    assert_eq!(human_duration(3600), "1:00:00");
+    assert_eq!(human_duration(45296), "12:34:56");
+}
+
+#[test]
+fn test_duration_parse() {
+    assert_eq!(duration_parse("45"), Some(45 * 60));
+    assert_eq!(duration_parse("30m"), Some(30 * 60));
+    assert_eq!(duration_parse("1h30m"), Some(90 * 60));
+    assert_eq!(duration_parse(" 90S "), Some(90));
+    assert_eq!(duration_parse("1h30"), None);
+    assert_eq!(duration_parse("m"), None);
+    assert_eq!(duration_parse("dir"), None);
+}
+
+#[test]
+fn test_human_parse() {
+    assert_eq!(human_parse("0"), Some(0));
+    assert_eq!(human_parse("4096"), Some(4096));
+    assert_eq!(human_parse("2kB"), Some(2048));
+    assert_eq!(human_parse("700M"), Some(700 * 1024 * 1024));
+    assert_eq!(human_parse("3.5g"), Some(3584 * 1024 * 1024));
+    assert_eq!(human_parse(" 1GB "), Some(1024 * 1024 * 1024));
+    assert_eq!(human_parse("-1M"), None);
+    assert_eq!(human_parse("MB"), None);
+    assert_eq!(human_parse("inf"), None);
+    assert_eq!(human_parse("NaNG"), None);
+    assert_eq!(human_parse("1e400M"), None);
+    assert_eq!(human_parse(""), None);
+}
+
+#[test]
+fn test_volumes_calculate() {
+    assert_eq!(volumes_calculate(&[], 10), Ok(vec![]));
+    assert_eq!(volumes_calculate(&[4, 4, 2, 1], 10), Ok(vec![1, 1, 1, 2]));
+    assert_eq!(volumes_calculate(&[10, 1, 9, 5], 10), Ok(vec![1, 2, 2, 3]));
+    assert_eq!(volumes_calculate(&[3, 11, 2], 10), Err(2));
+}
+
+#[test]
+fn test_chunk_name() {
+    assert_eq!(chunk_name(1, 99, 99, 2), "");
+    assert_eq!(chunk_name(1, 99, 250, 3), "001-099");
+    assert_eq!(chunk_name(99, 99, 250, 3), "001-099");
+    assert_eq!(chunk_name(100, 99, 250, 3), "100-198");
+    assert_eq!(chunk_name(250, 99, 250, 3), "199-250");
+    assert_eq!(chunk_name(7, 5, 7, 1), "6-7");
+}
+
+#[test]
+fn test_dir_census() {
+    let dir = TempDir::new().unwrap();
+
+    assert_eq!(dir_census(dir.path()), (0, 0));
+    fs::create_dir_all(dir.path().join("CD 1/Scans")).unwrap();
+    fs::write(dir.path().join("01.mp3"), [0; 10]).unwrap();
+    fs::write(dir.path().join("CD 1/02.mp3"), [0; 20]).unwrap();
+    fs::write(dir.path().join("CD 1/Scans/cover.jpg"), [0; 5]).unwrap();
+    assert_eq!(dir_census(dir.path()), (3, 35));
+    assert_eq!(dir_census(&dir.path().join("None")), (0, 0));
+}
+
+#[test]
+fn test_trash_url_encode() {
+    assert_eq!(
+        trash::url_encode(Path::new("/media/Book 1/01 - Über.mp3")),
+        "/media/Book%201/01%20-%20%C3%9Cber.mp3"
+    );
+    assert_eq!(trash::url_encode(Path::new("a_b-c.d~e")), "a_b-c.d~e");
+}
+
+#[test]
+fn test_trash_into() {
+    let dir = TempDir::new().unwrap();
+    let trash = dir.path().join("Trash");
+
+    let mut target = PathBuf::new();
+
+    for data in [b"one", b"two"] {
+        fs::create_dir(dir.path().join("Book 1")).unwrap();
+        fs::write(dir.path().join("Book 1/01.mp3"), data).unwrap();
+        target = trash::trash_into(
+            &dir.path().join("Book 1"),
+            &trash,
+            Path::new("/media/Book 1"),
+        )
+        .unwrap();
+    }
+    assert!(!dir.path().join("Book 1").exists());
+    assert_eq!(fs::read(trash.join("files/Book 1/01.mp3")).unwrap(), b"one");
+    assert_eq!(
+        fs::read(trash.join("files/Book 1 2/01.mp3")).unwrap(),
+        b"two"
+    );
+
+    assert_eq!(
+        trash::info_path(&target).unwrap(),
+        trash.join("info/Book 1 2.trashinfo")
+    );
+    let info = fs::read_to_string(trash.join("info/Book 1 2.trashinfo")).unwrap();
+    let lines: Vec<&str> = info.lines().collect();
+    assert_eq!(lines[..2], ["[Trash Info]", "Path=/media/Book%201"]);
+    assert!(lines[2].starts_with("DeletionDate=") && lines[2].len() == 32);
+}
+
+#[test]
+fn test_journal_undo() {
+    let dir = TempDir::new().unwrap();
+    let (dst, backup) = (dir.path().join("Book"), dir.path().join("Book.bak"));
+    let (track, gone) = (dst.join("01\tIntro\n.mp3"), dir.path().join("gone.mp3"));
+    let path = dir.path().join("run.journal");
+
+    fs::create_dir(&dst).unwrap();
+    fs::write(dst.join("old.mp3"), b"old").unwrap();
+    journal::open(&path).unwrap();
+    fs::rename(&dst, &backup).unwrap();
+    journal::record(journal::Entry::Moved(&dst, &backup));
+    fs::create_dir(&dst).unwrap();
+    journal::record(journal::Entry::Dir(&dst));
+    fs::write(&track, b"new").unwrap();
+    journal::record(journal::Entry::File(&track));
+    journal::record(journal::Entry::Removed(&gone));
+    let cover = dir.path().join("cover.jpg");
+    fs::write(&cover, b"jpg").unwrap();
+    let trashed = trash::trash_into(&cover, &dir.path().join("Trash"), &cover).unwrap();
+    journal::record(journal::Entry::Trashed(&cover, &trashed));
+
+    let (undone, complaints) = journal::undo(&path).unwrap();
+    assert_eq!(undone, 4);
+    assert_eq!(
+        complaints,
+        [format!("Removed for good: \"{}\"", gone.display())]
+    );
+    assert!(!backup.exists());
+    assert_eq!(fs::read(dst.join("old.mp3")).unwrap(), b"old");
+    assert!(!track.exists());
+    assert_eq!(fs::read(&cover).unwrap(), b"jpg");
+    assert!(!trash::info_path(&trashed).unwrap().exists());
+
+    // Undone already: nothing more to undo.
+    assert_eq!(journal::undo(&path).unwrap().0, 0);
+}
+
+#[test]
+fn test_journal_field_escape() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = Path::new(std::ffi::OsStr::from_bytes(b"/a\tb\nc\\d\xff e.mp3"));
+    let field = journal::field_escape(path);
+
+    assert_eq!(field, "/a\\tb\\nc\\\\d\\xff e.mp3");
+    assert_eq!(journal::field_unescape(&field).unwrap(), path);
+    assert_eq!(journal::field_unescape("/a\\q"), None);
+    assert_eq!(journal::field_unescape("/a\\x4"), None);
+}
+
+#[test]
+fn test_manifest_plan() {
+    use manifest::{Action, Record};
+
+    fn rec(dst: &str, src: &str, ii: u64) -> Record {
+        Record {
+            dst: PathBuf::from(dst),
+            src: PathBuf::from(src),
+            bytes: 100,
+            mtime: 1,
+            ii,
+            disc: "".to_string(),
+        }
+    }
+    let old = [rec("1-a.mp3", "/a.mp3", 1), rec("2-b.mp3", "/b.mp3", 2)];
+
+    assert_eq!(
+        manifest::plan(
+            &old,
+            &[rec("1-a.mp3", "/a.mp3", 1), rec("3-c.mp3", "/c.mp3", 3)]
+        ),
+        (
+            vec![Action::Keep, Action::Copy],
+            vec![PathBuf::from("2-b.mp3")]
+        )
+    );
+    assert_eq!(
+        manifest::plan(
+            &old,
+            &[rec("1-z.mp3", "/z.mp3", 1), rec("2-a.mp3", "/a.mp3", 2)]
+        ),
+        (
+            vec![Action::Copy, Action::Rename(PathBuf::from("1-a.mp3"), true)],
+            vec![PathBuf::from("2-b.mp3")]
+        )
+    );
+    assert_eq!(
+        manifest::plan(&old, &[]),
+        (
+            vec![],
+            vec![PathBuf::from("1-a.mp3"), PathBuf::from("2-b.mp3")]
+        )
+    );
+}
+
+#[test]
+fn test_manifest_is_order_broken() {
+    use manifest::Action;
+
+    assert!(!manifest::is_order_broken(&[]));
+    assert!(!manifest::is_order_broken(&[
+        Action::Keep,
+        Action::Copy,
+        Action::Copy
+    ]));
+    assert!(!manifest::is_order_broken(&[Action::Copy, Action::Copy]));
+    assert!(manifest::is_order_broken(&[Action::Copy, Action::Keep]));
+    assert!(manifest::is_order_broken(&[
+        Action::Keep,
+        Action::Copy,
+        Action::Rename(PathBuf::from("x"), false)
+    ]));
+}
+
+#[test]
+fn test_id3_tag_round_trip() {
+    for major in [3, 4] {
+        let tag = id3::tag_build(
+            major,
+            &[
+                id3::text_frame(major, "TIT2", "Ёлка"),
+                id3::text_frame(major, "TRCK", "7"),
+            ],
+        );
+        assert_eq!(id3::head_size(&tag[..10]), tag.len() as u64);
+
+        let (m, frames) = id3::frames_parse(&tag).unwrap();
+        assert_eq!(m, major);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(&frames[1].id, b"TRCK");
+        assert_eq!(id3::tag_build(m, &frames), tag);
+    }
+    assert_eq!(id3::head_size(b"RIFF\0\0\0\0\0\0"), 0);
+}
+
+#[test]
+fn test_id3_tail_size() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("01.mp3");
+    let ape = [&b"APETAGEX"[..], &[0xd0, 7, 0, 0, 32, 0, 0, 0], &[0; 16]].concat();
+    let v1 = [&b"TAG"[..], &[0; 125]].concat();
+    let sizes = |data: &[u8]| {
+        fs::write(&path, data).unwrap();
+        let mut file = fs::File::open(&path).unwrap();
+        let len = data.len() as u64;
+        (
+            id3::v1_size(&mut file, len).unwrap(),
+            id3::tail_size(&mut file, len).unwrap(),
+        )
+    };
+
+    assert_eq!(sizes(&[0xff; 200]), (0, 0));
+    assert_eq!(sizes(&[&[0xff; 200][..], &v1].concat()), (128, 128));
+    assert_eq!(sizes(&[&[0xff; 200][..], &ape, &v1].concat()), (128, 160));
+    assert_eq!(sizes(&v1[1..]), (0, 0));
+}
+
+#[test]
+fn test_meter_line() {
+    assert_eq!(
+        meter::line(0, 4, 0, 4096, 0.0),
+        "[--------------------] 0/4  0/4kB  0/s  ETA -:--"
+    );
+    assert_eq!(
+        meter::line(2, 4, 2048, 4096, 2.0),
+        "[##########----------] 2/4  2kB/4kB  1kB/s  ETA 0:02"
+    );
+    assert_eq!(
+        meter::line(4, 4, 4096, 4096, 4.0),
+        "[####################] 4/4  4kB/4kB  1kB/s  ETA 0:00"
+    );
+}
+
+#[test]
+fn test_progress_json_str() {
+    assert_eq!(progress::json_str(""), "\"\"");
+    assert_eq!(
+        progress::json_str("a \"b\" \\ c\n\u{1}"),
+        r#""a \"b\" \\ c\n\u0001""#
+    );
+}
+
+#[test]
+fn test_sniff_container_of() {
+    use sniff::{Container, OggCodec};
+
+    assert_eq!(sniff::container_of(b"fLaC\0\0\0\x22"), Container::Flac);
+    assert_eq!(sniff::container_of(b"\0\0\0\x20ftypM4A "), Container::Mp4);
+    assert_eq!(
+        sniff::container_of(&[0xff, 0xfb, 0x90, 0x64]),
+        Container::Mpeg
+    );
+    assert_eq!(
+        sniff::container_of(&[0xff, 0xf1, 0x50, 0x80]),
+        Container::Aac
+    );
+    assert_eq!(sniff::container_of(b"RIFF\0\0\0\0WAVEfmt "), Container::Wav);
+    assert_eq!(sniff::container_of(b"MAC \x96\x0f"), Container::Ape);
+    assert_eq!(
+        sniff::container_of(b"OggS"),
+        Container::Ogg(OggCodec::Other)
+    );
+    assert_eq!(sniff::container_of(b""), Container::Unknown);
+    assert_eq!(sniff::container_of(b"<html>"), Container::Unknown);
+
+    let mut opus = b"OggS".to_vec();
+    opus.resize(28, 0);
+    opus.extend(b"OpusHead");
+    assert_eq!(sniff::container_of(&opus), Container::Ogg(OggCodec::Opus));
+
+    assert!(sniff::is_ext_fit(Container::Mp4, "m4b"));
+    assert!(sniff::is_ext_fit(Container::Ogg(OggCodec::Opus), "ogg"));
+    assert!(!sniff::is_ext_fit(Container::Mp4, "mp3"));
+    assert!(!sniff::is_ext_fit(Container::Unknown, "mp3"));
+}
+
+#[test]
+fn test_sniff_ext_mismatch() {
+    let dir = TempDir::new().unwrap();
+    let file = |name: &str, data: &[u8]| {
+        let path = dir.path().join(name);
+        fs::write(&path, [data, &[0; 64]].concat()).unwrap();
+        path
+    };
+    let adts = [0xff, 0xf1, 0x50, 0x80];
+
+    assert_eq!(
+        sniff::ext_mismatch(&file("01.mp3", b"\0\0\0\x20ftypM4A ")),
+        Some((sniff::Container::Mp4, "m4a".to_string()))
+    );
+    assert_eq!(
+        sniff::ext_mismatch(&file("02.mp3", &adts)),
+        Some((sniff::Container::Aac, "aac".to_string()))
+    );
+    assert_eq!(
+        sniff::ext_mismatch(&file("03.m4b", b"\0\0\0\x20ftypM4B ")),
+        None
+    );
+    assert_eq!(sniff::ext_mismatch(&file("04.mp3", b"<html>")), None);
+    assert_eq!(sniff::ext_mismatch(&file("05", b"fLaC\0\0\0\x22")), None);
+
+    // Raw AAC gets fixed, but is no album track by its extension.
+    assert!(!is_audiofile_ext(Path::new("02.aac")));
+    assert!(is_audiofile_ext(Path::new("01.m4a")));
+}
+
+#[test]
+fn test_check_is_cbr() {
+    let dir = TempDir::new().unwrap();
+    let file = |name: &str, data: &[u8]| {
+        let path = dir.path().join(name);
+        fs::write(&path, [data, &[0; 128]].concat()).unwrap();
+        path
+    };
+    let header = [0xff, 0xfb, 0x90, 0x64];
+
+    assert!(check::is_cbr(&file("cbr.mp3", &header)).unwrap());
+    assert!(!check::is_cbr(&file("vbr.mp3", &[&header[..], &[0; 32], b"Xing"].concat())).unwrap());
+    assert!(!check::is_cbr(&file(
+        "vbri.mp3",
+        &[&header[..], &[0; 32], b"VBRI"].concat()
+    ))
+    .unwrap());
+    assert!(!check::is_cbr(&file("01.flac", b"fLaC\0\0\0\x22")).unwrap());
+}
+
+#[test]
+fn test_check_payload_hash_flac() {
+    let dir = TempDir::new().unwrap();
+    let file = |name: &str, data: &[u8]| {
+        let path = dir.path().join(name);
+        fs::write(&path, data).unwrap();
+        path
+    };
+    let flac = |md5: u8| [&b"fLaC\0\0\0\x22"[..], &[0; 18], &[md5; 16]].concat();
+    let tag = id3::tag_build(4, &[id3::text_frame(4, "TIT2", "Intro")]);
+
+    assert_eq!(
+        check::payload_hash(&file("01.flac", &flac(7))).unwrap(),
+        check::payload_hash(&file("02.flac", &[&tag[..], &flac(7)].concat())).unwrap()
+    );
+    assert_ne!(
+        check::payload_hash(&file("03.flac", &flac(7))).unwrap(),
+        check::payload_hash(&file("04.flac", &[&tag[..], &flac(8)].concat())).unwrap()
+    );
+}
+
+#[test]
+fn test_check_numbering_problems() {
+    assert!(check::numbering_problems(&[]).is_empty());
+    assert!(check::numbering_problems(&[Some(1), Some(2), Some(3)]).is_empty());
+    assert_eq!(
+        check::numbering_problems(&[Some(1), None, Some(4), Some(3), Some(3)]),
+        vec![
+            "1 track(s) without track number",
+            "duplicate track numbers: 3 (x2)",
+            "gaps in track numbers: 2",
+            "file order and tag order disagree at 1 place(s)",
+        ]
+    );
+}
+
+#[test]
+fn test_check_mixture() {
+    assert_eq!(check::mixture([44100, 44100].into_iter()), None);
+    assert_eq!(
+        check::mixture([48000, 44100, 44100].into_iter()),
+        Some("44100 (x2), 48000 (x1)".to_string())
+    );
+}
+
+#[test]
+fn test_cue_parse() {
+    let text = "\u{feff}PERFORMER \"The Band\"\r\nTITLE \"Live\"\r\nFILE \"Live.flac\" WAVE\r\n  TRACK 01 AUDIO\r\n    TITLE \"Intro\"\r\n    INDEX 01 00:00:00\r\n  TRACK 02 AUDIO\r\n    TITLE \"Song\"\r\n    PERFORMER Guest\r\n    INDEX 00 03:10:00\r\n    INDEX 01 03:12:37\r\n";
+    let sheet = cue::parse(&cue::decode(text.as_bytes())).unwrap();
+
+    assert_eq!(sheet.title.as_deref(), Some("Live"));
+    assert_eq!(sheet.performer.as_deref(), Some("The Band"));
+    assert_eq!(sheet.files[0].name, "Live.flac");
+    assert_eq!(sheet.files[0].tracks[1].title.as_deref(), Some("Song"));
+    assert_eq!(sheet.files[0].tracks[1].performer.as_deref(), Some("Guest"));
+    assert_eq!(sheet.files[0].tracks[1].start, (3 * 60 + 12) * 75 + 37);
+    assert_eq!(cue::samples_at(75, 44100), 44100);
+    assert!(cue::parse("TRACK 01 AUDIO").is_err());
+    assert!(cue::parse("FILE \"a.wav\" WAVE\nTRACK 01 AUDIO\nINDEX 01 00:61:00").is_err());
+}
+
+#[test]
+fn test_cue_mpeg_frame() {
+    assert_eq!(
+        cue::mpeg_frame(&[0xff, 0xfb, 0x90, 0x00]),
+        Some((417, 1152, 44100))
+    );
+    assert_eq!(
+        cue::mpeg_frame(&[0xff, 0xfb, 0x92, 0x00]),
+        Some((418, 1152, 44100))
+    );
+    assert_eq!(
+        cue::mpeg_frame(&[0xff, 0xf3, 0x80, 0x00]),
+        Some((208, 576, 22050))
+    );
+    assert_eq!(cue::mpeg_frame(&[0xff, 0xfb, 0xf0, 0x00]), None);
+}
+
+#[test]
+fn test_mp4_chapters() {
+    fn atom(kind: &[u8], body: &[u8]) -> Vec<u8> {
+        [&((body.len() + 8) as u32).to_be_bytes(), kind, body].concat()
+    }
+    let mut mvhd = vec![0_u8; 20];
+    mvhd[12..16].copy_from_slice(&1000_u32.to_be_bytes());
+    mvhd[16..20].copy_from_slice(&90_000_u32.to_be_bytes());
+    let mut chpl = vec![1, 0, 0, 0, 0, 0, 0, 0, 2];
+    for (start, title) in [(0_u64, "One"), (600_000_000, "Two")] {
+        chpl.extend(start.to_be_bytes());
+        chpl.push(title.len() as u8);
+        chpl.extend(title.as_bytes());
+    }
+    let moov = atom(
+        b"moov",
+        &[atom(b"mvhd", &mvhd), atom(b"udta", &atom(b"chpl", &chpl))].concat(),
+    );
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("book.m4b");
+    fs::write(
+        &path,
+        [
+            atom(b"ftyp", b"M4B \0\0\0\0"),
+            atom(b"mdat", &[0; 64]),
+            moov,
+        ]
+        .concat(),
+    )
+    .unwrap();
+
+    let chapters = mp4::chapters(&path).unwrap();
+    assert_eq!(chapters.len(), 2);
+    assert_eq!((chapters[0].start, chapters[0].end), (0, 60_000));
+    assert_eq!((chapters[1].start, chapters[1].end), (60_000, 90_000));
+    assert_eq!(chapters[1].title, "Two");
+    assert_eq!(
+        mp4::title_render("Chapter {n}: {title}", 2, "Two"),
+        "Chapter 2: Two"
+    );
+    assert!(mp4::ffmetadata(&chapters[..1]).ends_with("END=60000\ntitle=One\n"));
+
+    let backwards = mp4::Chapter {
+        start: 90_000,
+        end: 60_000,
+        title: "Three".to_string(),
+    };
+    let dst = dir.path().join("03.m4a");
+    assert!(!chapters[0].is_empty());
+    assert!(backwards.is_empty());
+    assert!(mp4::split(&path, &[backwards], &[&dst]).is_ok());
+    assert!(!dst.exists());
+}
+
+#[test]
+fn test_merge_flac_number() {
+    assert_eq!(merge::flac_number(0), vec![0x00]);
+    assert_eq!(merge::flac_number(0x7f), vec![0x7f]);
+    assert_eq!(merge::flac_number(0x80), vec![0xc2, 0x80]);
+    assert_eq!(merge::flac_number(0x800), vec![0xe0, 0xa0, 0x80]);
+    assert_eq!(merge::flac_number(1 << 35).len(), 7);
+}
+
+#[test]
+fn test_merge_mp3_constant_bitrate() {
+    let dir = TempDir::new().unwrap();
+    let frame = |bitrate_ii: u8| {
+        let header = [0xff, 0xfb, bitrate_ii << 4, 0x44];
+        let mut frame = header.to_vec();
+        frame.resize(cue::mpeg_frame(&header).unwrap().0 as usize, 0x11);
+        frame
+    };
+    let (a, b, dst) = (
+        dir.path().join("a.mp3"),
+        dir.path().join("b.mp3"),
+        dir.path().join("ab.mp3"),
+    );
+
+    fs::write(&a, frame(9).repeat(200)).unwrap();
+    fs::write(&b, frame(9).repeat(3)).unwrap();
+    assert!(merge::mp3(&[&a, &b], &dst).is_ok());
+    assert_eq!(fs::read(&dst).unwrap(), frame(9).repeat(203));
+    fs::write(&b, [frame(9), frame(10)].concat()).unwrap();
+    assert!(merge::mp3(&[&a, &b], &dst).is_err());
+}
+
+#[test]
+fn test_playlist_render() {
+    let entries = [
+        playlist::Entry {
+            path: PathBuf::from("01-Intro.mp3"),
+            seconds: Some(61),
+            title: "1 Intro".to_string(),
+        },
+        playlist::Entry {
+            path: PathBuf::from("CD 2/02-Rock & Roll.mp3"),
+            seconds: None,
+            title: "Rock & Roll".to_string(),
+        },
+    ];
+
+    assert_eq!(
+        playlist::m3u8(&entries),
+        "#EXTM3U\n#EXTINF:61,1 Intro\n01-Intro.mp3\n#EXTINF:-1,Rock & Roll\nCD 2/02-Rock & Roll.mp3\n"
+    );
+    assert!(playlist::pls(&entries).ends_with("Length2=-1\nNumberOfEntries=2\nVersion=2\n"));
+    let xspf = playlist::xspf(&entries);
+    assert!(xspf.contains("<location>CD%202/02-Rock%20%26%20Roll.mp3</location>"));
+    assert!(xspf.contains("<title>Rock &amp; Roll</title>"));
+    assert!(xspf.contains("<duration>61000</duration>"));
+}
+
+#[test]
+fn test_transcode_rule_parse() {
+    let rule = transcode::rule_parse("flac, .ape:MP3").unwrap();
+    assert_eq!(rule.from, ["FLAC", "APE"]);
+    assert_eq!(rule.to, "mp3");
+    assert_eq!(rule.template[0], "ffmpeg");
+    assert!(rule.template.contains(&"libmp3lame".to_string()));
+
+    let rule = transcode::rule_parse("wma:mp3:lame --quiet -b 64 -m m {src} {dst}").unwrap();
+    assert_eq!(
+        rule.template,
+        ["lame", "--quiet", "-b", "64", "-m", "m", "{src}", "{dst}"]
+    );
+
+    let rule = transcode::rule_parse(
+        r#"flac:opus:opusenc --comment 'x=a b' --artist "J. \"D\"" a\ b {src} {dst}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        rule.template,
+        [
+            "opusenc",
+            "--comment",
+            "x=a b",
+            "--artist",
+            "J. \"D\"",
+            "a b",
+            "{src}",
+            "{dst}"
+        ]
+    );
+    assert_eq!(transcode::words_split(" '' \"\" ").unwrap(), ["", ""]);
+    assert!(transcode::rule_parse("flac:mp3:lame 'x {src} {dst}").is_err());
+
+    assert!(transcode::rule_parse("flac").is_err());
+    assert!(transcode::rule_parse(":mp3").is_err());
+    assert!(transcode::rule_parse("flac:xyz").is_err());
+    assert!(transcode::rule_parse("flac:mp3:lame {src}").is_err());
+}
+
+#[test]
+fn test_loudness() {
+    // EBU Tech 3341: a 1 kHz stereo sine at -23 dBFS is -23 LUFS.
+    let amplitude = 10_f32.powf(-23.0 / 20.0);
+    let samples: Vec<f32> = (0..loudness::RATE * 10)
+        .flat_map(|i| {
+            let x = amplitude
+                * (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / loudness::RATE as f32).sin();
+            [x, x]
+        })
+        .collect();
+    let mut meter = loudness::Meter::new(loudness::RATE, 2);
+    meter.add(&samples);
+    let measured = meter.finish();
+
+    assert!((loudness::integrated(measured.blocks.iter()).unwrap() + 23.0).abs() < 0.1);
+    assert_eq!(
+        loudness::gain_tags(false, &measured, &[&measured])[0],
+        ("REPLAYGAIN_TRACK_GAIN", "4.99 dB".to_string())
+    );
+    assert_eq!(
+        loudness::gain_tags(true, &measured, &[&measured])[0],
+        ("R128_TRACK_GAIN", "-2".to_string())
+    );
+
+    let silence = loudness::Meter::new(loudness::RATE, 1).finish();
+    assert!(loudness::gain_tags(false, &silence, &[&silence]).is_empty());
+}
+
+#[test]
+fn test_archive_tar_unpack() {
+    fn entry(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut header = [0_u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+        header[156] = b'0';
+        let padding = vec![0; (512 - data.len() % 512) % 512];
+        [&header[..], data, &padding].concat()
+    }
+
+    assert_eq!(
+        archive::stem(Path::new("/tmp/Book.TAR.GZ")).unwrap(),
+        "Book"
+    );
+    assert_eq!(
+        archive::kind(Path::new("Book.tgz")),
+        Some(archive::Kind::Tar(Some("gzip")))
+    );
+    assert_eq!(archive::kind(Path::new("Book.mp3")), None);
+
+    let tar = [
+        entry("Book/CD 1/01.mp3", b"one"),
+        entry("Book/cover.jpg", b"jpeg"),
+        entry("Book/CD 2/02.FLAC", b"two"),
+        entry("../evil.mp3", b"evil"),
+        vec![0; 1024],
+    ];
+    let dir = TempDir::new().unwrap();
+
+    assert_eq!(
+        archive::tar_unpack(&tar[..3].concat()[..], dir.path(), &["mp3", "flac"]).unwrap(),
+        2
+    );
+    assert_eq!(
+        fs::read(dir.path().join("Book/CD 1/01.mp3")).unwrap(),
+        b"one"
+    );
+    assert_eq!(
+        fs::read(dir.path().join("Book/CD 2/02.FLAC")).unwrap(),
+        b"two"
+    );
+    assert!(!dir.path().join("Book/cover.jpg").exists());
+    assert!(archive::tar_unpack(&tar.concat()[..], dir.path(), &["mp3"]).is_err());
+
+    let path = dir.path().join("Book.tar");
+    fs::write(&path, [&tar[..3].concat()[..], &tar[4]].concat()).unwrap();
+    assert_eq!(
+        archive::list(&path, &["mp3", "flac"]).unwrap(),
+        [
+            ("Book/CD 1/01.mp3".to_string(), 3),
+            ("Book/CD 2/02.FLAC".to_string(), 3)
+        ]
+    );
+
+    let (to, copy) = (dir.path().join("stream"), dir.path().join("copy.flac"));
+    let mut entries = vec![];
+    assert!(archive::is_random(&path));
+    assert_eq!(
+        archive::unpack_each(&path, &to, &["mp3", "flac"], |file, entry| {
+            fs::write(file, b"")?; // Done away with.
+            entries.push(entry);
+            Ok(())
+        })
+        .unwrap(),
+        2
+    );
+    assert_eq!(entries[1].name, "Book/CD 2/02.FLAC");
+    archive::entry_copy(&path, &entries[1], &copy).unwrap();
+    assert_eq!(fs::read(&copy).unwrap(), b"two");
+}
+
+#[test]
+fn test_batch_conflicts() {
+    let args = |extra: &[&str]| {
+        args_command().try_get_matches_from(
+            ["procrustes", "--batch"]
+                .iter()
+                .chain(extra)
+                .chain(&["albums.txt", "/media/car"]),
+        )
+    };
+
+    assert!(args(&[]).is_ok());
+    assert!(args(&["-u", "Book"]).is_err());
+    assert!(args(&["-p"]).is_err());
+}
+
+#[test]
+fn test_batch_album_args() {
+    let args = |extra: &[&str]| {
+        args_command()
+            .try_get_matches_from(
+                ["procrustes", "--batch"]
+                    .iter()
+                    .chain(extra)
+                    .chain(&["albums.txt", "/media/car"]),
+            )
+            .unwrap()
+    };
+    let album = Path::new("/books/Dune");
+
+    assert_eq!(
+        batch_album_args(&args(&["-y", "-v"]), album, 1, None),
+        ["--verbose", "--dry-run", "--", "/books/Dune", "/media/car"]
+    );
+    assert_eq!(
+        batch_album_args(
+            &args(&["-e", "ogg", "--journal", "j.txt"]),
+            album,
+            2,
+            Some(7)
+        ),
+        [
+            "--file-type=ogg",
+            "--journal=j.txt.2",
+            "--album-num=7",
+            "--",
+            "/books/Dune",
+            "/media/car"
+        ]
+    );
+}
+
+#[test]
+fn test_batch_albums_listed() {
+    let dir = TempDir::new().unwrap();
+    let list = dir.path().join("albums.txt");
+
+    fs::write(
+        &list,
+        "# To the car:\nBook 2\n\n  Book 1  \n/media/Book 3\n",
+    )
+    .unwrap();
+    assert_eq!(
+        batch_albums(&list),
+        [
+            dir.path().join("Book 2"),
+            dir.path().join("Book 1"),
+            PathBuf::from("/media/Book 3")
+        ]
+    );
+}
+
+#[test]
+fn test_duplicates_find() {
+    let order: Vec<PathBuf> = ["a/01.mp3", "a/02.mp3", "a/03.mp3", "a/04.mp3"]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+    let hashes: HashMap<PathBuf, u64> = [(&order[0], 1), (&order[1], 2), (&order[3], 1)]
+        .iter()
+        .map(|(p, h)| (p.to_path_buf(), *h))
+        .collect();
+
+    assert_eq!(
+        duplicates_find(&order, false, &hashes),
+        [(order[0].clone(), order[3].clone())]
+    );
+    assert_eq!(
+        duplicates_find(&order, true, &hashes),
+        [(order[3].clone(), order[0].clone())]
+    );
+    assert!(duplicates_find(&order, false, &HashMap::new()).is_empty());
+}
+
+#[test]
+fn test_duplicates_find_sources() {
+    let srcs = [PathBuf::from("/m/Book"), PathBuf::from("/m/Book 2")];
+    let order: Vec<PathBuf> = [
+        "/m/Book/01.mp3",
+        "/m/Book/CD/02.mp3",
+        "/m/Book 2/01.mp3",
+        "/m/Book 2/CD/02.mp3",
+    ]
+    .iter()
+    .map(PathBuf::from)
+    .collect();
+    let hashes: HashMap<PathBuf, u64> = order
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (p.clone(), i as u64 % 2))
+        .collect();
+    let natural = [
+        (order[0].clone(), order[2].clone()),
+        (order[1].clone(), order[3].clone()),
+    ];
+    let reversed: Vec<PathBuf> = order.iter().rev().cloned().collect();
+
+    // The first source comes first, the walk reversed, or not.
+    assert_eq!(duplicates_find(&order, false, &hashes), natural);
+    assert_eq!(duplicates_find(&reversed, true, &hashes), natural);
+
+    assert_eq!(src_relative(&srcs, &order[1]), Path::new("CD/02.mp3"));
+    assert_eq!(src_relative(&srcs, &order[3]), Path::new("CD/02.mp3"));
+    assert_eq!(
+        src_relative(&srcs, Path::new("/m/Other/01.mp3")),
+        Path::new("/m/Other/01.mp3")
+    );
+}
+
 #[test]
 fn test_initials() {
     assert_eq!(initials(""), "");
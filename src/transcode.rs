@@ -0,0 +1,135 @@
+use std::{fs, io, path::Path, process};
+
+/// Transcoding rule: the files with one of the [from] extensions (upper case)
+/// become [to] (lower case) files by the command [template].
+///
+#[derive(Debug, PartialEq)]
+pub struct Rule {
+    pub from: Vec<String>,
+    pub to: String,
+    pub template: Vec<String>,
+}
+
+/// Returns the command template, used for [ext] files, unless told otherwise.
+///
+pub fn default_template(ext: &str) -> Option<String> {
+    let codec = match ext {
+        "mp3" => "libmp3lame -q:a 4",
+        "opus" => "libopus -b:a 64k",
+        "ogg" => "libvorbis -q:a 4",
+        "m4a" | "m4b" => "aac -b:a 128k",
+        "flac" => "flac",
+        "wav" => "pcm_s16le",
+        _ => return None,
+    };
+    Some(format!(
+        "ffmpeg -v error -nostdin -y -i {{src}} -map 0:a:0 -c:a {} {{dst}}",
+        codec
+    ))
+}
+
+/// Returns the words of the [command], split the way a shell does it:
+/// by whitespace, except inside single, or double quotes; a backslash
+/// escapes the next character, save inside single quotes.
+///
+pub fn words_split(command: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('\''), '\'') | (Some('"'), '"') => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                let next = chars
+                    .next()
+                    .ok_or(format!("nothing to escape at the end of \"{}\"", command))?;
+                word.get_or_insert_with(String::new).push(next);
+            }
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'') | (None, '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(format!("unclosed quote in \"{}\"", command));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Parses the [rule], "FROM[,FROM...]:TO[:TEMPLATE]", e.g. "flac,ape:mp3", or
+/// "wma:mp3:lame --quiet -b 64 -m m {src} {dst}"; {src} and {dst} in the template
+/// are replaced by the file paths; the template words are quoted as in a shell
+/// (see words_split()).
+///
+pub fn rule_parse(rule: &str) -> Result<Rule, String> {
+    let mut parts = rule.splitn(3, ':');
+    let from: Vec<String> = parts
+        .next()
+        .unwrap()
+        .split(',')
+        .map(|e| e.trim().trim_start_matches('.').to_uppercase())
+        .filter(|e| !e.is_empty())
+        .collect();
+    let to = parts
+        .next()
+        .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+        .filter(|e| !e.is_empty())
+        .ok_or(format!("no target format in \"{}\"", rule))?;
+    let template = match parts.next().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        Some(template) => template.to_string(),
+        None => default_template(&to).ok_or(format!("no command for .{} files", to))?,
+    };
+
+    if from.is_empty() {
+        return Err(format!("no source formats in \"{}\"", rule));
+    }
+    if !template.contains("{src}") || !template.contains("{dst}") {
+        return Err(format!("no {{src}}, or {{dst}} in \"{}\"", template));
+    }
+    Ok(Rule {
+        from,
+        to,
+        template: words_split(&template)?,
+    })
+}
+
+/// Transcodes [src] to [dst] according to the [rule]; no [dst] left behind,
+/// if it fails.
+///
+pub fn run(rule: &Rule, src: &Path, dst: &Path) -> io::Result<()> {
+    let args: Vec<String> = rule
+        .template
+        .iter()
+        .map(|a| {
+            a.replace("{src}", &src.to_string_lossy())
+                .replace("{dst}", &dst.to_string_lossy())
+        })
+        .collect();
+    let result = process::Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(process::Stdio::null())
+        .output();
+
+    match result {
+        Ok(out) if out.status.success() && dst.is_file() => Ok(()),
+        Ok(out) => {
+            fs::remove_file(dst).ok();
+            Err(io::Error::other(format!(
+                "{} failed: {}",
+                args[0],
+                String::from_utf8_lossy(&out.stderr).trim()
+            )))
+        }
+        Err(e) => {
+            fs::remove_file(dst).ok();
+            Err(io::Error::new(e.kind(), format!("{}: {}", args[0], e)))
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use chrono::Local;
+use std::{
+    env, fs,
+    io::{self, Write},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+/// Returns the home trash directory, according to the XDG trash specification.
+///
+fn home_trash() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|h| PathBuf::from(h).join(".local/share")))
+        .map(|d| d.join("Trash"))
+}
+
+/// Returns the topmost directory of the file system [path] resides on.
+///
+fn mount_top(path: &Path) -> PathBuf {
+    let dev = path.metadata().map_or(0, |m| m.dev());
+    let mut top = path.to_path_buf();
+
+    while let Some(parent) = top.parent() {
+        if parent.metadata().map_or(true, |m| m.dev() != dev) {
+            break;
+        }
+        top = parent.to_path_buf();
+    }
+    top
+}
+
+/// Percent-encodes [path] for the Path key of a .trashinfo file.
+///
+pub fn url_encode(path: &Path) -> String {
+    path.to_string_lossy()
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Moves [path] into the [trash] directory, [recorded] being the original
+/// location to be stored in the trash info. Returns the new location.
+///
+pub fn trash_into(path: &Path, trash: &Path, recorded: &Path) -> io::Result<PathBuf> {
+    let files = trash.join("files");
+    let info = trash.join("info");
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+
+    let name = path.file_name().unwrap().to_string_lossy().to_string();
+    let mut stem = name.clone();
+    let mut n = 1;
+    let info_file = loop {
+        // Claim the name by creating the info file first, as the spec suggests.
+        let info_file = info.join(format!("{}.trashinfo", stem));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&info_file)
+        {
+            Ok(mut f) => {
+                write!(
+                    f,
+                    "[Trash Info]\nPath={}\nDeletionDate={}\n",
+                    url_encode(recorded),
+                    Local::now().format("%Y-%m-%dT%H:%M:%S")
+                )?;
+                break info_file;
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                n += 1;
+                stem = format!("{} {}", name, n);
+            }
+            Err(e) => return Err(e),
+        }
+    };
+    let target = files.join(&stem);
+
+    fs::rename(path, &target).inspect_err(|_| {
+        fs::remove_file(&info_file).ok();
+    })?;
+    Ok(target)
+}
+
+/// Returns the trash info file of [target], a path in the trash
+/// (see trash_into()).
+///
+pub fn info_path(target: &Path) -> Option<PathBuf> {
+    let mut name = target.file_name()?.to_os_string();
+
+    name.push(".trashinfo");
+    Some(target.parent()?.parent()?.join("info").join(name))
+}
+
+/// Moves [path] to the XDG trash: to the home trash, if it is on the same
+/// file system, otherwise to the .Trash-$uid directory on top of the [path]
+/// file system (removable media). Returns the new location.
+///
+pub fn trash_move(path: &Path) -> io::Result<PathBuf> {
+    if let Some(home) = home_trash() {
+        if let Ok(target) = trash_into(path, &home, path) {
+            return Ok(target);
+        }
+    }
+    let top = mount_top(path);
+    // Owned by the process user; the home directory, where there is no /proc.
+    let uid = fs::metadata("/proc/self")
+        .or_else(|_| fs::metadata(env::var_os("HOME").unwrap_or_default()))?
+        .uid();
+
+    trash_into(
+        path,
+        &top.join(format!(".Trash-{}", uid)),
+        path.strip_prefix(&top).unwrap(),
+    )
+}